@@ -13,6 +13,7 @@ const CR_LF: [u8; 2] = [b'\r', b'\n'];
 pub struct Reader<R> {
     check_end: bool,
     eof: bool,
+    last_chunk: bool,
     err: Option<Error>,
     n: usize,
     reader: BufReader<R>,
@@ -105,6 +106,7 @@ where
         Self {
             check_end: false,
             eof: false,
+            last_chunk: false,
             err: None,
             n: 0,
             reader: BufReader::new(reader),
@@ -121,12 +123,21 @@ where
             }
         };
 
+        // an empty line means that the underlying reader has ended
+        let empty = line.is_empty();
+
         match parse_hex_uint(line) {
             Ok(v) => self.n = v,
             Err(err) => self.err = Some(Error::new(ErrorKind::Other, err)),
         }
 
         self.eof = self.n == 0;
+        self.last_chunk = self.eof && !empty && self.err.is_none();
+    }
+
+    /// Checks if the last, zero-sized chunk was read, i.e. the body was not truncated.
+    pub fn is_complete(&self) -> bool {
+        self.last_chunk
     }
 
     fn chunk_header_avaliable(&self) -> bool {
@@ -222,6 +233,7 @@ mod tests {
         io::copy(&mut reader, &mut writer).expect("failed to dechunk");
 
         assert_eq!("hello, world! 0123456789abcdef".as_bytes(), &writer[..]);
+        assert!(reader.is_complete());
     }
     #[test]
     fn read_multiple() {
@@ -252,6 +264,7 @@ mod tests {
         io::copy(&mut reader, &mut writer).expect("failed to dechunk");
 
         assert_eq!("1234567".as_bytes(), &writer[..]);
+        assert!(!reader.is_complete());
     }
     #[test]
     fn read_ignore_extensions() {
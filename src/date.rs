@@ -0,0 +1,222 @@
+//! HTTP-dates, as used in `Date`, `Expires` or `Last-Modified` headers
+use std::prelude::v1::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+///Parses an HTTP-date in any of the formats allowed by RFC 9110: IMF-fixdate,
+///e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, the obsolete RFC 850 format, e.g.
+///`Sunday, 06-Nov-94 08:49:37 GMT`, or ANSI C's asctime() format, e.g.
+///`Sun Nov  6 08:49:37 1994`. Returns `None` if `s` isn't a valid date.
+pub fn parse(s: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+
+    let (day, month, year, time) = match parts[..] {
+        [_, day, month, year, time, "GMT"] if parts[0].ends_with(',') => {
+            (day, month, self::year(year)?, time)
+        }
+        [_, date, time, "GMT"] if parts[0].ends_with(',') => {
+            let mut date = date.split('-');
+            let (day, month, year) = (date.next()?, date.next()?, date.next()?);
+
+            if date.next().is_some() || year.len() != 2 {
+                return None;
+            }
+
+            let year: i64 = year.parse().ok()?;
+            (
+                day,
+                month,
+                if year < 70 { 2000 + year } else { 1900 + year },
+                time,
+            )
+        }
+        [_, month, day, time, year] => (day, month, self::year(year)?, time),
+        _ => return None,
+    };
+
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let day: i64 = number(day, 1, days_in_month(year, month))?;
+
+    let mut time = time.split(':');
+    let hour = number(time.next()?, 0, 23)?;
+    let min = number(time.next()?, 0, 59)?;
+    let sec = number(time.next()?, 0, 60)?;
+
+    if time.next().is_some() {
+        return None;
+    }
+
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + min * 60 + sec;
+
+    match secs {
+        s if s >= 0 => UNIX_EPOCH.checked_add(Duration::from_secs(s as u64)),
+        s => UNIX_EPOCH.checked_sub(Duration::from_secs(s.unsigned_abs())),
+    }
+}
+
+///Formats `time` as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, discarding
+///fractions of a second.
+pub fn format(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => {
+            let before = e.duration();
+            -(before.as_secs() as i64) - i64::from(before.subsec_nanos() > 0)
+        }
+    };
+
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+//Parses a number of at most 2 digits, from `min` to `max` inclusive.
+fn number(s: &str, min: i64, max: i64) -> Option<i64> {
+    if s.is_empty() || s.len() > 2 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    s.parse().ok().filter(|n| (min..=max).contains(n))
+}
+
+//Parses a year of 4 digits.
+fn year(s: &str) -> Option<i64> {
+    if s.len() != 4 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    s.parse().ok()
+}
+
+//Returns the number of days in `month` of `year`.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+//Returns the number of days from 1970-01-01 to the given date of the proleptic
+//Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+//Returns the date of the proleptic Gregorian calendar, which is `days` after 1970-01-01,
+//as year, month and day.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Sun, 06 Nov 1994 08:49:37 GMT
+    const EXAMPLE: u64 = 784_111_777;
+
+    #[test]
+    fn parse_formats() {
+        const VALID: [&str; 4] = [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+        ];
+
+        for s in VALID.iter() {
+            assert_eq!(parse(s), Some(UNIX_EPOCH + Duration::from_secs(EXAMPLE)));
+        }
+
+        assert_eq!(parse("Thu, 01 Jan 1970 00:00:00 GMT"), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse("Tue, 29 Feb 2000 23:59:59 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_868_799))
+        );
+        assert_eq!(
+            parse("Wed, 31 Dec 1969 23:59:59 GMT"),
+            Some(UNIX_EPOCH - Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn format_date() {
+        const CASES: [(i64, &str); 5] = [
+            (EXAMPLE as i64, "Sun, 06 Nov 1994 08:49:37 GMT"),
+            (0, "Thu, 01 Jan 1970 00:00:00 GMT"),
+            (951_868_799, "Tue, 29 Feb 2000 23:59:59 GMT"),
+            (-1, "Wed, 31 Dec 1969 23:59:59 GMT"),
+            (4_102_444_800, "Fri, 01 Jan 2100 00:00:00 GMT"),
+        ];
+
+        for (secs, s) in CASES.iter() {
+            let time = match *secs {
+                secs if secs >= 0 => UNIX_EPOCH + Duration::from_secs(secs as u64),
+                secs => UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()),
+            };
+
+            assert_eq!(format(time), *s);
+            assert_eq!(parse(s), Some(time));
+        }
+
+        assert_eq!(
+            format(UNIX_EPOCH + Duration::from_millis(1500)),
+            "Thu, 01 Jan 1970 00:00:01 GMT"
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        const INVALID: [&str; 13] = [
+            "",
+            "0",
+            "-1",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Tue, 29 Feb 2001 00:00:00 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sunday, 06-Nov-1994 08:49:37 GMT",
+            "Sun, 06 Nov 94 08:49:37 GMT",
+            "Sun, 06 Nov +994 08:49:37 GMT",
+            "Sun, 06 Nov 9223372036854775807 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 -9223372036854775808",
+        ];
+
+        for s in INVALID.iter() {
+            assert_eq!(parse(s), None, "{}", s);
+        }
+    }
+}
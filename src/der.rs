@@ -0,0 +1,188 @@
+//! minimal DER reader, just enough to inspect X.509 certificates
+use std::prelude::v1::*;
+use crate::error::ParseErr;
+
+pub(crate) const BOOLEAN: u8 = 0x01;
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const SEQUENCE: u8 = 0x30;
+pub(crate) const CONTEXT_0: u8 = 0xA0;
+pub(crate) const CONTEXT_3: u8 = 0xA3;
+
+///Reads consecutive TLV (tag, length, value) elements from a DER-encoded slice.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    ///Returns tag of the next element without consuming it.
+    pub(crate) fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    ///Reads next element. Returns its tag, its value and the whole encoding of the element.
+    pub(crate) fn read_raw(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), ParseErr> {
+        let tag = *self.data.first().ok_or(ParseErr::Empty)?;
+        let first_len = *self.data.get(1).ok_or(ParseErr::Invalid)?;
+
+        let (len, header_len) = if first_len < 0x80 {
+            (first_len as usize, 2)
+        } else {
+            let num_bytes = (first_len & 0x7F) as usize;
+            if num_bytes == 0 || num_bytes > 4 {
+                return Err(ParseErr::Invalid);
+            }
+
+            let bytes = self.data.get(2..2 + num_bytes).ok_or(ParseErr::Invalid)?;
+            let len = bytes.iter().fold(0, |len, b| (len << 8) | *b as usize);
+            (len, 2 + num_bytes)
+        };
+
+        let end = header_len.checked_add(len).ok_or(ParseErr::Invalid)?;
+        if end > self.data.len() {
+            return Err(ParseErr::Invalid);
+        }
+
+        let (element, rest) = self.data.split_at(end);
+        self.data = rest;
+
+        Ok((tag, &element[header_len..], element))
+    }
+
+    ///Reads next element and checks that it has the `expected` tag. Returns its value.
+    pub(crate) fn read(&mut self, expected: u8) -> Result<&'a [u8], ParseErr> {
+        match self.read_raw()? {
+            (tag, value, _) if tag == expected => Ok(value),
+            _ => Err(ParseErr::Invalid),
+        }
+    }
+}
+
+///Returns the fields of `tbsCertificate` from a DER-encoded X.509 certificate,
+///positioned at `subjectPublicKeyInfo`.
+fn tbs_certificate(cert: &[u8]) -> Result<Reader<'_>, ParseErr> {
+    let cert = Reader::new(cert).read(SEQUENCE)?;
+    let mut tbs = Reader::new(Reader::new(cert).read(SEQUENCE)?);
+
+    if tbs.peek_tag() == Some(CONTEXT_0) {
+        tbs.read(CONTEXT_0)?; // version
+    }
+
+    tbs.read(INTEGER)?; // serialNumber
+    tbs.read(SEQUENCE)?; // signature
+    tbs.read(SEQUENCE)?; // issuer
+    tbs.read(SEQUENCE)?; // validity
+    tbs.read(SEQUENCE)?; // subject
+
+    Ok(tbs)
+}
+
+///Returns the whole DER encoding of `SubjectPublicKeyInfo` of a X.509 certificate.
+pub(crate) fn subject_public_key_info(cert: &[u8]) -> Result<&[u8], ParseErr> {
+    match tbs_certificate(cert)?.read_raw()? {
+        (SEQUENCE, _, spki) => Ok(spki),
+        _ => Err(ParseErr::Invalid),
+    }
+}
+
+///Returns value of the extension of a X.509 certificate identified by `oid`,
+///given as DER-encoded content of an object identifier.
+pub(crate) fn extension<'a>(cert: &'a [u8], oid: &[u8]) -> Result<Option<&'a [u8]>, ParseErr> {
+    let mut tbs = tbs_certificate(cert)?;
+    tbs.read(SEQUENCE)?; // subjectPublicKeyInfo
+
+    while !tbs.is_empty() {
+        let (tag, value, _) = tbs.read_raw()?;
+
+        if tag == CONTEXT_3 {
+            let mut extensions = Reader::new(Reader::new(value).read(SEQUENCE)?);
+
+            while !extensions.is_empty() {
+                let mut extension = Reader::new(extensions.read(SEQUENCE)?);
+                let id = extension.read(OID)?;
+
+                if extension.peek_tag() == Some(BOOLEAN) {
+                    extension.read(BOOLEAN)?; // critical
+                }
+
+                let value = extension.read(OCTET_STRING)?;
+
+                if id == oid {
+                    return Ok(Some(value));
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT_DER: &[u8] = include_bytes!("../tests/certs/ca.der");
+    const RA_TLS_CERT_DER: &[u8] = include_bytes!("../tests/certs/ratls.der");
+    const RA_TLS_QUOTE: &[u8] = include_bytes!("../tests/certs/ratls_quote.bin");
+    const QUOTE_OID: [u8; 9] = [0x2A, 0x86, 0x48, 0x86, 0xF8, 0x4D, 0x01, 0x0D, 0x01];
+    const BASIC_CONSTRAINTS_OID: [u8; 3] = [0x55, 0x1D, 0x13];
+
+    #[test]
+    fn reader_read_raw() {
+        let data = [0x02, 0x01, 0x05, 0x04, 0x82, 0x00, 0x01, 0xFF];
+        let mut reader = Reader::new(&data);
+
+        assert_eq!(reader.read_raw(), Ok((0x02, &data[2..3], &data[..3])));
+        assert_eq!(reader.read_raw(), Ok((0x04, &data[7..], &data[3..])));
+        assert!(reader.is_empty());
+        assert_eq!(reader.read_raw(), Err(ParseErr::Empty));
+    }
+
+    #[test]
+    fn reader_read_invalid() {
+        assert_eq!(
+            Reader::new(&[0x30, 0x05, 0x00]).read_raw(),
+            Err(ParseErr::Invalid)
+        );
+        assert_eq!(
+            Reader::new(&[0x30, 0x80]).read_raw(),
+            Err(ParseErr::Invalid)
+        );
+        assert_eq!(
+            Reader::new(&[0x02, 0x00]).read(SEQUENCE),
+            Err(ParseErr::Invalid)
+        );
+    }
+
+    #[test]
+    fn spki_from_cert() {
+        let spki = subject_public_key_info(CERT_DER).unwrap();
+
+        assert_eq!(spki[0], SEQUENCE);
+        assert!(CERT_DER.windows(spki.len()).any(|w| w == spki));
+        assert!(subject_public_key_info(&CERT_DER[..40]).is_err());
+    }
+
+    #[test]
+    fn extension_from_cert() {
+        assert_eq!(
+            extension(RA_TLS_CERT_DER, &QUOTE_OID),
+            Ok(Some(RA_TLS_QUOTE))
+        );
+        assert_eq!(
+            extension(RA_TLS_CERT_DER, &BASIC_CONSTRAINTS_OID),
+            Ok(Some(&[0x30, 0x00][..]))
+        );
+        assert_eq!(extension(CERT_DER, &QUOTE_OID), Ok(None));
+        assert!(extension(&RA_TLS_CERT_DER[..300], &QUOTE_OID).is_err());
+    }
+}
@@ -0,0 +1,618 @@
+//! parallel downloads, fetching segments of a file with `Range` requests over several connections
+use std::prelude::v1::*;
+use crate::{
+    error::{self, ParseErr},
+    range::{self, ContentRange},
+    request::OwnedRequest,
+    response::Response,
+};
+use ring::digest::{Context, Digest, SHA256};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fs::{self, File, OpenOptions};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::fs::{self, File, OpenOptions};
+use std::{
+    convert::TryFrom,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    panic,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+const DEFAULT_CONNECTIONS: usize = 4;
+const DEFAULT_SEGMENT_SIZE: u64 = 1 << 20;
+const DEFAULT_RETRIES: usize = 3;
+
+///Downloader fetching a file in segments, over several connections at once
+///
+///The first segment is requested with `Range`, which also tells the complete length
+///of the file. The remaining segments are then fetched in parallel, each with its own
+///connection, and written into the file at their offsets. They're requested with
+///`If-Range` set to `ETag` or `Last-Modified` of the first response, so a segment fails
+///if the file changes during the download. If the first response has no strong validator,
+///i.e. neither a strong `ETag` nor, without `ETag`, `Last-Modified` at least a minute before
+///`Date`, segments of different versions couldn't be told apart, so the whole file
+///is requested again and written over a single connection.
+///
+///A segment, whose transfer fails or which the server can't serve at the moment,
+///responding with `5xx` status, is requested again, up to the set number of retries.
+///Once all segments are written, the size of the file and, if it's set, its SHA-256
+///digest are verified. Until then, the file is written next to its destination, with
+///`.part` appended to its name, and it's removed if the download fails.
+///
+///If the server doesn't support ranges and responds with `200 OK`, the whole body
+///is written to the file over a single connection.
+///
+///# Examples
+///```
+///use http_req::{download::Downloader, request::OwnedRequest};
+///use std::env;
+///
+///let request = OwnedRequest::new("https://www.rust-lang.org/learn".parse().unwrap());
+///let path = env::temp_dir().join("learn.html");
+///
+///let response = Downloader::new(request)
+///    .connections(8)
+///    .segment_size(64 * 1024)
+///    .download_to_file(&path)
+///    .unwrap();
+///
+///println!("Status: {}", response.status_code());
+///```
+#[derive(Clone)]
+pub struct Downloader {
+    request: OwnedRequest,
+    connections: usize,
+    segment_size: u64,
+    retries: usize,
+    sha256: Option<[u8; 32]>,
+}
+
+impl Downloader {
+    ///Creates a new `Downloader` of the resource requested with `request`.
+    ///
+    ///By default, it uses 4 connections, 1 MiB segments and retries a failed segment
+    ///3 times.
+    pub fn new(request: OwnedRequest) -> Downloader {
+        Downloader {
+            request,
+            connections: DEFAULT_CONNECTIONS,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            retries: DEFAULT_RETRIES,
+            sha256: None,
+        }
+    }
+
+    ///Sets the maximum number of connections used at once, at least 1.
+    pub fn connections(&mut self, connections: usize) -> &mut Self {
+        self.connections = connections.max(1);
+        self
+    }
+
+    ///Sets size of a segment, in bytes, at least 1.
+    pub fn segment_size(&mut self, size: u64) -> &mut Self {
+        self.segment_size = size.max(1);
+        self
+    }
+
+    ///Sets how many times a segment is requested again after its transfer fails.
+    pub fn retries(&mut self, retries: usize) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    ///Sets the expected SHA-256 digest of the file.
+    ///
+    ///If the digest of the downloaded file is different, the download fails
+    ///with `InvalidData`.
+    pub fn sha256(&mut self, digest: [u8; 32]) -> &mut Self {
+        self.sha256 = Some(digest);
+        self
+    }
+
+    ///Downloads the file to `path`, replacing its content, and returns the response
+    ///to the first request. `path` is written only once the download is verified.
+    ///
+    ///If the server responds with status other than `200 OK`, `206 Partial Content`
+    ///or `416 Range Not Satisfiable` for an empty file, the response is returned
+    ///and the file isn't created.
+    pub fn download_to_file<P: AsRef<Path>>(&self, path: P) -> Result<Response, error::Error> {
+        let path = path.as_ref();
+        let part = match path.file_name() {
+            Some(name) => {
+                let mut name = name.to_os_string();
+                name.push(".part");
+                path.with_file_name(name)
+            }
+            None => return Err(io::Error::new(ErrorKind::InvalidInput, "path isn't a file").into()),
+        };
+
+        let result = self.download(&part).and_then(|(res, downloaded)| {
+            if downloaded {
+                fs::rename(&part, path)?;
+            }
+            Ok(res)
+        });
+
+        if result.is_err() {
+            let _ = fs::remove_file(&part);
+        }
+
+        result
+    }
+
+    //Downloads the file to `path` and verifies it. Returns the response to the first
+    //request and whether the file was written.
+    fn download(&self, path: &Path) -> Result<(Response, bool), error::Error> {
+        let mut request = self.request.clone();
+        request.range(0..self.segment_size);
+
+        let (mut res, mut body) = request.as_request().open()?;
+        let mut status: u16 = res.status_code().into();
+
+        if status == 206 && range::validator(&res).is_none() {
+            let (whole, whole_body) = self.request.as_request().open()?;
+            res = whole;
+            body = whole_body;
+            status = res.status_code().into();
+        }
+
+        let validator = range::validator(&res);
+
+        match (status, res.content_range(), validator) {
+            (
+                206,
+                Some(ContentRange::Satisfied {
+                    first: 0,
+                    last,
+                    complete_length: Some(len),
+                }),
+                Some(validator),
+            ) => {
+                let mut file = create(path)?;
+                file.set_len(len)?;
+
+                let mut segments = Vec::new();
+                let mut written = 0;
+
+                match io::copy(&mut body.take(last + 1), &mut file) {
+                    Ok(n) if n == last + 1 => written += n,
+                    _ => segments.push((0, last)),
+                }
+
+                segments.extend(
+                    (last + 1..len)
+                        .step_by(usize::try_from(self.segment_size).unwrap_or(usize::MAX))
+                        .map(|first| (first, len.min(first.saturating_add(self.segment_size)) - 1)),
+                );
+
+                file.flush()?;
+                written += self.fetch(path, segments, &validator)?;
+
+                if written != len {
+                    return Err(
+                        io::Error::new(ErrorKind::UnexpectedEof, "file is incomplete").into(),
+                    );
+                }
+            }
+            (200, ..) => {
+                let mut file = create(path)?;
+                let written = io::copy(&mut body, &mut file)?;
+                file.flush()?;
+
+                if matches!(res.content_len(), Some(len) if len as u64 != written) {
+                    return Err(
+                        io::Error::new(ErrorKind::UnexpectedEof, "file is incomplete").into(),
+                    );
+                }
+            }
+            (416, Some(ContentRange::Unsatisfied(0)), _) => {
+                create(path)?;
+            }
+            (206, ..) => return Err(ParseErr::HeadersErr.into()),
+            _ => return Ok((res, false)),
+        }
+
+        if let Some(expected) = self.sha256 {
+            if sha256(path)?.as_ref() != expected {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "SHA-256 digest of the file doesn't match",
+                )
+                .into());
+            }
+        }
+
+        Ok((res, true))
+    }
+
+    //Fetches `segments` of the file at `path` in parallel. Returns the number
+    //of written bytes.
+    fn fetch(
+        &self,
+        path: &Path,
+        segments: Vec<(u64, u64)>,
+        validator: &str,
+    ) -> Result<u64, error::Error> {
+        let mut request = self.request.clone();
+        request.header("If-Range", validator);
+
+        let connections = self.connections.min(segments.len());
+        let shared = Arc::new(Shared {
+            request,
+            segments,
+            path: path.to_path_buf(),
+            retries: self.retries,
+            next: AtomicUsize::new(0),
+            written: AtomicU64::new(0),
+            failed: AtomicBool::new(false),
+        });
+
+        let workers: Vec<_> = (0..connections)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || shared.work())
+            })
+            .collect();
+
+        let mut result = Ok(());
+
+        for worker in workers {
+            match worker.join() {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => result = Err(e),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+
+        result.map(|_| shared.written.load(Ordering::SeqCst))
+    }
+}
+
+//State shared by the threads fetching segments of a file
+struct Shared {
+    request: OwnedRequest,
+    segments: Vec<(u64, u64)>,
+    path: PathBuf,
+    retries: usize,
+    next: AtomicUsize,
+    written: AtomicU64,
+    failed: AtomicBool,
+}
+
+impl Shared {
+    //Fetches segments, one by one, until none are left or any of them fails.
+    fn work(&self) -> Result<(), error::Error> {
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+
+        while !self.failed.load(Ordering::SeqCst) {
+            let segment = match self.segments.get(self.next.fetch_add(1, Ordering::SeqCst)) {
+                Some(segment) => *segment,
+                None => return Ok(()),
+            };
+
+            let mut attempt = 0;
+
+            loop {
+                match self.fetch_segment(&mut file, segment) {
+                    Ok(()) => break,
+                    Err(Failure::Transient(_)) if attempt < self.retries => attempt += 1,
+                    Err(Failure::Transient(e)) | Err(Failure::Fatal(e)) => {
+                        self.failed.store(true, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    //Requests the bytes from `first` to `last` and writes them into `file` at `first`.
+    fn fetch_segment(&self, file: &mut File, (first, last): (u64, u64)) -> Result<(), Failure> {
+        let mut request = self.request.clone();
+        request.range(first..=last);
+
+        let (res, body) = request.as_request().open()?;
+
+        match u16::from(res.status_code()) {
+            206 => (),
+            500..=599 => return Err(Failure::Transient(ParseErr::StatusErr.into())),
+            _ => return Err(Failure::Fatal(ParseErr::StatusErr.into())),
+        }
+
+        match res.content_range() {
+            Some(ContentRange::Satisfied {
+                first: f, last: l, ..
+            }) if f == first && l == last => (),
+            _ => return Err(Failure::Fatal(ParseErr::HeadersErr.into())),
+        }
+
+        let len = last - first + 1;
+        file.seek(SeekFrom::Start(first))?;
+
+        if io::copy(&mut body.take(len), file)? != len {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "segment is incomplete").into());
+        }
+
+        file.flush()?;
+        self.written.fetch_add(len, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+//Failure of a segment transfer
+enum Failure {
+    //The segment may be transferred if it's requested again: the connection failed
+    //or the server responded with `5xx` status.
+    Transient(error::Error),
+    Fatal(error::Error),
+}
+
+impl From<error::Error> for Failure {
+    fn from(e: error::Error) -> Failure {
+        match e {
+            error::Error::IO(_) => Failure::Transient(e),
+            e => Failure::Fatal(e),
+        }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Failure {
+        Failure::Transient(e.into())
+    }
+}
+
+//Computes SHA-256 digest of the file at `path`.
+fn sha256(path: &Path) -> io::Result<Digest> {
+    let mut file = File::open(path)?;
+    let mut context = Context::new(&SHA256);
+    let mut buf = [0; 8192];
+
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(context.finish()),
+            n => context.update(&buf[..n]),
+        }
+    }
+}
+
+//Creates the file at `path`, truncating it if it exists.
+fn create(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{response::StatusCode, test_server, uri::Uri};
+    use ring::digest::digest;
+    use std::{
+        env,
+        net::{TcpListener, TcpStream},
+    };
+
+    const DATA_LEN: usize = 1000;
+
+    fn data() -> Vec<u8> {
+        (0..DATA_LEN).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(digest(&SHA256, data).as_ref());
+        hash
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("http_req_download_{}", name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    //Reply to a request, selected by its number
+    #[derive(Clone, Copy, PartialEq)]
+    enum Reply {
+        Full,
+        //The body is cut in half.
+        Truncated,
+        //503 Service Unavailable
+        Unavailable,
+    }
+
+    //Serves `data()` with `etag` on every connection, honoring a single `Range` if `ranges`
+    //is set. Requests are answered as `reply` selects for their numbers. Returns uri
+    //of the server and the number of received requests.
+    fn serve(
+        etag: &'static str,
+        ranges: bool,
+        reply: fn(usize) -> Reply,
+    ) -> (Uri, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/file", listener.local_addr().unwrap());
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let stream = stream.unwrap();
+                thread::spawn(move || respond(stream, etag, ranges, reply(n)));
+            }
+        });
+
+        (uri.parse().unwrap(), count)
+    }
+
+    fn respond(mut stream: TcpStream, etag: &str, ranges: bool, reply: Reply) {
+        let head = match test_server::read_head(&mut stream) {
+            Ok(head) => head,
+            Err(_) => return,
+        };
+
+        if reply == Reply::Unavailable {
+            let _ =
+                stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n");
+            return;
+        }
+
+        let range = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Range: bytes="))
+            .filter(|_| ranges)
+            .map(|range| {
+                let (first, last) = range.split_at(range.find('-').unwrap());
+                let first: usize = first.parse().unwrap();
+                let last: usize = last[1..].parse().unwrap();
+                (first, last.min(DATA_LEN - 1))
+            });
+
+        let data = data();
+        let (status, body) = match range {
+            Some((first, last)) => (
+                format!(
+                    "206 Partial Content\r\nContent-Range: bytes {}-{}/{}",
+                    first, last, DATA_LEN
+                ),
+                &data[first..=last],
+            ),
+            None => ("200 OK".to_string(), &data[..]),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nETag: {}\r\nContent-Length: {}\r\n\r\n",
+            status,
+            etag,
+            body.len()
+        );
+
+        let _ = stream.write_all(response.as_bytes());
+        let body = if reply == Reply::Truncated {
+            &body[..body.len() / 2]
+        } else {
+            body
+        };
+        let _ = stream.write_all(body);
+    }
+
+    #[test]
+    fn downloader_setters() {
+        let mut downloader =
+            Downloader::new(OwnedRequest::new("http://localhost".parse().unwrap()));
+        downloader.connections(0).segment_size(0).retries(5);
+
+        assert_eq!(downloader.connections, 1);
+        assert_eq!(downloader.segment_size, 1);
+        assert_eq!(downloader.retries, 5);
+    }
+
+    #[test]
+    fn download_segments() {
+        let (uri, count) = serve("\"v1\"", true, |n| match n {
+            0 | 4 => Reply::Truncated,
+            6 => Reply::Unavailable,
+            _ => Reply::Full,
+        });
+        let path = temp_path("segments");
+
+        let res = Downloader::new(OwnedRequest::new(uri))
+            .connections(3)
+            .segment_size(100)
+            .sha256(sha256(&data()))
+            .download_to_file(&path)
+            .unwrap();
+
+        assert_eq!(res.status_code(), StatusCode::new(206));
+        assert_eq!(fs::read(&path).unwrap(), data());
+        assert_eq!(count.load(Ordering::SeqCst), 13);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn download_without_ranges() {
+        let (uri, count) = serve("\"v1\"", false, |_| Reply::Full);
+        let path = temp_path("without_ranges");
+
+        let res = Downloader::new(OwnedRequest::new(uri))
+            .segment_size(100)
+            .download_to_file(&path)
+            .unwrap();
+
+        assert_eq!(res.status_code(), StatusCode::new(200));
+        assert_eq!(fs::read(&path).unwrap(), data());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn download_weak_etag() {
+        let (uri, count) = serve("W/\"v1\"", true, |_| Reply::Full);
+        let path = temp_path("weak_etag");
+
+        let res = Downloader::new(OwnedRequest::new(uri))
+            .segment_size(100)
+            .download_to_file(&path)
+            .unwrap();
+
+        assert_eq!(res.status_code(), StatusCode::new(200));
+        assert_eq!(fs::read(&path).unwrap(), data());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn download_sha256_mismatch() {
+        let (uri, _) = serve("\"v1\"", true, |_| Reply::Full);
+        let path = temp_path("sha256_mismatch");
+
+        let err = Downloader::new(OwnedRequest::new(uri))
+            .segment_size(300)
+            .sha256([0; 32])
+            .download_to_file(&path)
+            .unwrap_err();
+
+        match err {
+            error::Error::IO(e) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(!path.exists());
+        assert!(!path.with_extension("part").exists());
+    }
+
+    #[test]
+    fn download_retries_exhausted() {
+        let (uri, count) = serve("\"v1\"", true, |n| match n {
+            0 => Reply::Full,
+            _ => Reply::Truncated,
+        });
+        let path = temp_path("retries_exhausted");
+
+        let err = Downloader::new(OwnedRequest::new(uri))
+            .connections(1)
+            .segment_size(500)
+            .retries(2)
+            .download_to_file(&path)
+            .unwrap_err();
+
+        match err {
+            error::Error::IO(e) => assert_eq!(e.kind(), ErrorKind::UnexpectedEof),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+        assert!(!path.exists());
+        assert!(!path.with_extension("part").exists());
+    }
+}
@@ -0,0 +1,214 @@
+//! entity tags, used in `ETag`, `If-Match` and `If-None-Match` headers
+use std::prelude::v1::*;
+use crate::error::ParseErr;
+use std::{fmt, str};
+
+///Entity tag: an opaque validator of a representation, which may be weak
+///
+///# Examples
+///```
+///use http_req::etag::ETag;
+///
+///let etag: ETag = "W/\"xyzzy\"".parse().unwrap();
+///
+///assert!(etag.is_weak());
+///assert_eq!(etag.tag(), "xyzzy");
+///assert_eq!(etag.to_string(), "W/\"xyzzy\"");
+///```
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct ETag {
+    weak: bool,
+    tag: String,
+}
+
+impl ETag {
+    ///Creates a strong `ETag` with the opaque `tag`, which is given without quotes.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::etag::ETag;
+    ///
+    ///let etag = ETag::strong("xyzzy").unwrap();
+    ///assert_eq!(etag.to_string(), "\"xyzzy\"");
+    ///```
+    pub fn strong(tag: &str) -> Result<ETag, ParseErr> {
+        ETag::new(tag, false)
+    }
+
+    ///Creates a weak `ETag` with the opaque `tag`, which is given without quotes.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::etag::ETag;
+    ///
+    ///let etag = ETag::weak("xyzzy").unwrap();
+    ///assert_eq!(etag.to_string(), "W/\"xyzzy\"");
+    ///```
+    pub fn weak(tag: &str) -> Result<ETag, ParseErr> {
+        ETag::new(tag, true)
+    }
+
+    //Creates `ETag` with `tag`, if it contains only characters allowed in entity tags.
+    fn new(tag: &str, weak: bool) -> Result<ETag, ParseErr> {
+        if !tag
+            .bytes()
+            .all(|b| b == 0x21 || (0x23..=0x7E).contains(&b) || b >= 0x80)
+        {
+            return Err(ParseErr::Invalid);
+        }
+
+        Ok(ETag {
+            weak,
+            tag: tag.to_string(),
+        })
+    }
+
+    ///Returns the opaque tag, without quotes.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    ///Checks if this `ETag` is weak.
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    ///Compares this `ETag` with `other` using the strong comparison: both have to be strong
+    ///and their tags have to be the same. It's used for `If-Match` and `If-Range`.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::etag::ETag;
+    ///
+    ///let strong = ETag::strong("1").unwrap();
+    ///let weak = ETag::weak("1").unwrap();
+    ///
+    ///assert!(strong.strong_eq(&ETag::strong("1").unwrap()));
+    ///assert!(!strong.strong_eq(&weak));
+    ///assert!(!weak.strong_eq(&weak));
+    ///```
+    pub fn strong_eq(&self, other: &ETag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    ///Compares this `ETag` with `other` using the weak comparison: their tags have
+    ///to be the same, regardless of being weak. It's used for `If-None-Match`.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::etag::ETag;
+    ///
+    ///let strong = ETag::strong("1").unwrap();
+    ///let weak = ETag::weak("1").unwrap();
+    ///
+    ///assert!(strong.weak_eq(&weak));
+    ///assert!(!weak.weak_eq(&ETag::weak("2").unwrap()));
+    ///```
+    pub fn weak_eq(&self, other: &ETag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl fmt::Display for ETag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/")?;
+        }
+
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+impl str::FromStr for ETag {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<ETag, ParseErr> {
+        let s = s.trim();
+        let (weak, quoted) = match s.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, s),
+        };
+
+        if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+            return Err(ParseErr::Invalid);
+        }
+
+        ETag::new(&quoted[1..quoted.len() - 1], weak)
+    }
+}
+
+//Returns value of `If-Match` or `If-None-Match` with `etags`, or `*` if there are none.
+pub(crate) fn header_value(etags: &[ETag]) -> String {
+    match etags {
+        [] => "*".to_string(),
+        _ => etags
+            .iter()
+            .map(ETag::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etag_from_str() {
+        const VALID: [(&str, bool, &str); 4] = [
+            ("\"xyzzy\"", false, "xyzzy"),
+            ("W/\"xyzzy\"", true, "xyzzy"),
+            (" \"\" ", false, ""),
+            ("\"a!#~\"", false, "a!#~"),
+        ];
+        const INVALID: [&str; 6] = [
+            "xyzzy",
+            "\"xyzzy",
+            "w/\"xyzzy\"",
+            "W/xyzzy",
+            "\"a\"b\"",
+            "\"",
+        ];
+
+        for (s, weak, tag) in VALID.iter() {
+            let etag: ETag = s.parse().unwrap();
+
+            assert_eq!(etag.is_weak(), *weak);
+            assert_eq!(etag.tag(), *tag);
+            assert_eq!(etag.to_string(), s.trim());
+        }
+
+        for s in INVALID.iter() {
+            assert_eq!(s.parse::<ETag>(), Err(ParseErr::Invalid));
+        }
+
+        assert_eq!(ETag::strong("a b"), Err(ParseErr::Invalid));
+    }
+
+    #[test]
+    fn etag_compare() {
+        const CASES: [(&str, &str, bool, bool); 4] = [
+            ("W/\"1\"", "W/\"1\"", false, true),
+            ("W/\"1\"", "W/\"2\"", false, false),
+            ("W/\"1\"", "\"1\"", false, true),
+            ("\"1\"", "\"1\"", true, true),
+        ];
+
+        for (a, b, strong, weak) in CASES.iter() {
+            let (a, b): (ETag, ETag) = (a.parse().unwrap(), b.parse().unwrap());
+
+            assert_eq!(a.strong_eq(&b), *strong);
+            assert_eq!(b.strong_eq(&a), *strong);
+            assert_eq!(a.weak_eq(&b), *weak);
+            assert_eq!(b.weak_eq(&a), *weak);
+        }
+    }
+
+    #[test]
+    fn etag_header_value() {
+        let etags = ["\"1\"".parse().unwrap(), "W/\"2\"".parse().unwrap()];
+
+        assert_eq!(header_value(&etags), "\"1\", W/\"2\"");
+        assert_eq!(header_value(&[]), "*");
+    }
+}
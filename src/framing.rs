@@ -0,0 +1,161 @@
+//! framing of response bodies, as described in RFC 7230, section 3.3.3
+use std::prelude::v1::*;
+use crate::{error::ParseErr, request::Method, response::Response};
+
+///Way in which the end of a response body is determined
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Framing {
+    ///Response has no body, whatever its headers say.
+    Empty,
+    ///Body is encoded with the chunked transfer coding. Other transfer codings,
+    ///applied before it (e.g. `gzip, chunked`), are left in place.
+    Chunked,
+    ///Body has the given length in bytes.
+    Length(usize),
+    ///Body ends when the server closes the connection.
+    Close,
+}
+
+impl Framing {
+    ///Determines framing of the body of `res`, which is a response to a request with `method`.
+    ///Fails if `Content-Length` is invalid or has conflicting values.
+    pub fn of(res: &Response, method: &Method) -> Result<Framing, ParseErr> {
+        let status = res.status_code();
+
+        if *method == Method::HEAD
+            || status.is_info()
+            || status.is(|code| code == 204 || code == 304)
+            || (*method == Method::CONNECT && status.is_success())
+        {
+            return Ok(Framing::Empty);
+        }
+
+        if let Some(codings) = res.headers().get("Transfer-Encoding") {
+            return Ok(if is_chunked(codings) {
+                Framing::Chunked
+            } else {
+                Framing::Close
+            });
+        }
+
+        match res.headers().get("Content-Length") {
+            Some(len) => content_length(len).map(Framing::Length),
+            None => Ok(Framing::Close),
+        }
+    }
+}
+
+//Checks if `chunked` is the final coding in the list of transfer `codings`.
+fn is_chunked(codings: &str) -> bool {
+    let last = codings.rsplit(',').map(str::trim).find(|c| !c.is_empty());
+
+    matches!(last, Some(coding) if coding.eq_ignore_ascii_case("chunked"))
+}
+
+//Parses value of `Content-Length`. A list of identical values, which results from repeated
+//header fields, is accepted as a single value.
+fn content_length(value: &str) -> Result<usize, ParseErr> {
+    let mut len = None;
+
+    for val in value.split(',').map(str::trim) {
+        if val.is_empty() || !val.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseErr::HeadersErr);
+        }
+
+        let val = val.parse()?;
+
+        match len {
+            Some(len) if len != val => return Err(ParseErr::HeadersErr),
+            _ => len = Some(val),
+        }
+    }
+
+    len.ok_or(ParseErr::HeadersErr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framing(head: &str, method: Method) -> Result<Framing, ParseErr> {
+        let res = Response::from_head(head.as_bytes()).unwrap();
+        Framing::of(&res, &method)
+    }
+
+    #[test]
+    fn framing_empty() {
+        const HEADS: [&str; 5] = [
+            "HTTP/1.1 100 Continue\r\n\r\n",
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n",
+            "HTTP/1.1 204 No Content\r\nContent-Length: 10\r\n\r\n",
+            "HTTP/1.1 304 Not Modified\r\nTransfer-Encoding: chunked\r\n\r\n",
+            "HTTP/1.1 304 Not Modified\r\nContent-Length: invalid\r\n\r\n",
+        ];
+
+        for head in HEADS.iter() {
+            assert_eq!(framing(head, Method::GET), Ok(Framing::Empty));
+        }
+
+        let head = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n";
+        assert_eq!(framing(head, Method::HEAD), Ok(Framing::Empty));
+        assert_eq!(framing(head, Method::CONNECT), Ok(Framing::Empty));
+
+        let head = "HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 10\r\n\r\n";
+        assert_eq!(framing(head, Method::CONNECT), Ok(Framing::Length(10)));
+    }
+
+    #[test]
+    fn framing_transfer_encoding() {
+        const CASES: [(&str, Framing); 5] = [
+            ("chunked", Framing::Chunked),
+            ("gzip, chunked", Framing::Chunked),
+            ("gzip,Chunked ,", Framing::Chunked),
+            ("chunked, gzip", Framing::Close),
+            ("gzip", Framing::Close),
+        ];
+
+        for (codings, expected) in CASES.iter() {
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: {}\r\nContent-Length: 10\r\n\r\n",
+                codings
+            );
+            assert_eq!(framing(&head, Method::GET), Ok(*expected));
+        }
+    }
+
+    #[test]
+    fn framing_content_length() {
+        const CASES: [(&str, Result<Framing, ParseErr>); 7] = [
+            ("0", Ok(Framing::Length(0))),
+            ("42", Ok(Framing::Length(42))),
+            ("42, 42", Ok(Framing::Length(42))),
+            ("42, 43", Err(ParseErr::HeadersErr)),
+            ("+42", Err(ParseErr::HeadersErr)),
+            ("4 2", Err(ParseErr::HeadersErr)),
+            ("42,", Err(ParseErr::HeadersErr)),
+        ];
+
+        for (len, expected) in CASES.iter() {
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", len);
+            assert_eq!(&framing(&head, Method::GET), expected);
+        }
+
+        let head = "HTTP/1.1 200 OK\r\nContent-Length: 42\r\nContent-Length: 43\r\n\r\n";
+        assert_eq!(framing(head, Method::GET), Err(ParseErr::HeadersErr));
+
+        let head = "HTTP/1.1 200 OK\r\nContent-Length: 99999999999999999999999\r\n\r\n";
+        assert!(framing(head, Method::GET).is_err());
+    }
+
+    #[test]
+    fn framing_close() {
+        assert_eq!(
+            framing("HTTP/1.0 200 OK\r\n\r\n", Method::GET),
+            Ok(Framing::Close)
+        );
+        assert_eq!(
+            framing("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n", Method::POST),
+            Ok(Framing::Close)
+        );
+    }
+}
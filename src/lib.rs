@@ -22,10 +22,31 @@
 #[macro_use]
 extern crate sgx_tstd as std;
 
+#[cfg(all(feature = "native-tls", feature = "rust-tls"))]
+compile_error!("features `native-tls` and `rust-tls` are mutually exclusive");
+#[cfg(all(feature = "native-tls", feature = "mesalock_sgx"))]
+compile_error!("feature `native-tls` is not supported with `mesalock_sgx`");
+
+pub mod cache;
+pub mod client;
+pub mod download;
 pub mod error;
+pub mod etag;
+pub mod range;
+#[cfg(feature = "rust-tls")]
+pub mod ratls;
 pub mod request;
 pub mod response;
+pub mod sse;
+#[cfg(any(feature = "native-tls", feature = "rust-tls"))]
 pub mod tls;
 pub mod uri;
+pub mod websocket;
 
 mod chunked;
+mod date;
+#[cfg(feature = "rust-tls")]
+mod der;
+mod framing;
+#[cfg(test)]
+mod test_server;
@@ -0,0 +1,652 @@
+//! byte ranges: `Range` requests, `206 Partial Content` responses and resumable downloads
+use std::prelude::v1::*;
+use crate::{
+    date,
+    error::{self, ParseErr},
+    request::Request,
+    response::{find_slice, Headers, Response, StatusCode, CR_LF_2},
+};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fs::{self, OpenOptions};
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::fs::{self, OpenOptions};
+use std::{
+    fmt,
+    io::{self, Write},
+    ops::{Range, RangeFrom, RangeInclusive},
+    path::{Path, PathBuf},
+    str,
+    time::Duration,
+};
+
+const CR_LF: &[u8] = b"\r\n";
+//How long before `Date` a resource has to be last modified, so that `Last-Modified`
+//is a strong validator (RFC 9110, section 8.8.2.2).
+const STRONG_DATE_AGE: Duration = Duration::from_secs(60);
+
+///Range of bytes requested with the `Range` header
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ByteRange {
+    ///Bytes from the first to the last position, inclusive, e.g. `0-499`
+    Bounded(u64, u64),
+    ///Bytes from the position to the end, e.g. `500-`
+    From(u64),
+    ///Given number of bytes at the end, e.g. `-500`
+    Suffix(u64),
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ByteRange::Bounded(first, last) => write!(f, "{}-{}", first, last),
+            ByteRange::From(first) => write!(f, "{}-", first),
+            ByteRange::Suffix(len) => write!(f, "-{}", len),
+        }
+    }
+}
+
+impl From<Range<u64>> for ByteRange {
+    fn from(range: Range<u64>) -> ByteRange {
+        ByteRange::Bounded(range.start, range.end.saturating_sub(1))
+    }
+}
+
+impl From<RangeInclusive<u64>> for ByteRange {
+    fn from(range: RangeInclusive<u64>) -> ByteRange {
+        ByteRange::Bounded(*range.start(), *range.end())
+    }
+}
+
+impl From<RangeFrom<u64>> for ByteRange {
+    fn from(range: RangeFrom<u64>) -> ByteRange {
+        ByteRange::From(range.start)
+    }
+}
+
+///Formats value of the `Range` header requesting `ranges`.
+pub(crate) fn header_value(ranges: &[ByteRange]) -> String {
+    let ranges: Vec<_> = ranges.iter().map(ByteRange::to_string).collect();
+    format!("bytes={}", ranges.join(", "))
+}
+
+///Range of bytes sent in a response, described by the `Content-Range` header
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ContentRange {
+    ///Bytes from the first to the last position, inclusive, of a representation
+    ///with the complete length, if it's known, e.g. `bytes 0-499/1234`
+    Satisfied {
+        first: u64,
+        last: u64,
+        complete_length: Option<u64>,
+    },
+    ///None of the requested ranges could be satisfied by a representation
+    ///with the given complete length, e.g. `bytes */1234`
+    Unsatisfied(u64),
+}
+
+impl ContentRange {
+    ///Returns the number of bytes in this range.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::range::ContentRange;
+    ///
+    ///let range: ContentRange = "bytes 0-499/1234".parse().unwrap();
+    ///assert_eq!(range.len(), 500);
+    ///```
+    pub fn len(&self) -> u64 {
+        match self {
+            ContentRange::Satisfied { first, last, .. } => last - first + 1,
+            ContentRange::Unsatisfied(_) => 0,
+        }
+    }
+
+    ///Checks if this range is empty, i.e. it's unsatisfied.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///Returns the complete length of the representation, if it's known.
+    pub fn complete_length(&self) -> Option<u64> {
+        match self {
+            ContentRange::Satisfied {
+                complete_length, ..
+            } => *complete_length,
+            ContentRange::Unsatisfied(len) => Some(*len),
+        }
+    }
+}
+
+impl str::FromStr for ContentRange {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<ContentRange, ParseErr> {
+        let s = s.trim();
+
+        if s.len() < 6 || !s[..6].eq_ignore_ascii_case("bytes ") {
+            return Err(ParseErr::Invalid);
+        }
+
+        let mut parts = s[6..].trim_start().splitn(2, '/');
+        let range = parts.next().unwrap_or("");
+        let complete_length = match parts.next().ok_or(ParseErr::Invalid)? {
+            "*" => None,
+            len => Some(parse_u64(len)?),
+        };
+
+        if range == "*" {
+            return complete_length
+                .map(ContentRange::Unsatisfied)
+                .ok_or(ParseErr::Invalid);
+        }
+
+        let mut bounds = range.splitn(2, '-');
+        let first = parse_u64(bounds.next().unwrap_or(""))?;
+        let last = parse_u64(bounds.next().ok_or(ParseErr::Invalid)?)?;
+
+        if first > last || matches!(complete_length, Some(len) if last >= len) {
+            return Err(ParseErr::Invalid);
+        }
+
+        Ok(ContentRange::Satisfied {
+            first,
+            last,
+            complete_length,
+        })
+    }
+}
+
+impl fmt::Display for ContentRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContentRange::Satisfied {
+                first,
+                last,
+                complete_length: Some(len),
+            } => write!(f, "bytes {}-{}/{}", first, last, len),
+            ContentRange::Satisfied { first, last, .. } => write!(f, "bytes {}-{}/*", first, last),
+            ContentRange::Unsatisfied(len) => write!(f, "bytes */{}", len),
+        }
+    }
+}
+
+//Parses a number made of digits only.
+fn parse_u64(s: &str) -> Result<u64, ParseErr> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseErr::Invalid);
+    }
+
+    Ok(s.parse()?)
+}
+
+///Part of a `206 Partial Content` response, containing a single range of bytes
+#[derive(Debug, PartialEq, Clone)]
+pub struct Part {
+    ///Range of bytes contained in this part
+    pub range: ContentRange,
+    ///Headers of this part: headers of the response, if it contains only one part,
+    ///or headers of the body part of a `multipart/byteranges` response
+    pub headers: Headers,
+    ///Bytes of this part
+    pub body: Vec<u8>,
+}
+
+///Splits `body` of a `206 Partial Content` response `res` into parts, containing
+///single ranges. The response contains either a single part, described by its
+///`Content-Range` header, or several of them in a `multipart/byteranges` body.
+///
+///# Examples
+///```
+///use http_req::{range, request::Request, uri::Uri};
+///
+///let uri: Uri = "https://www.rust-lang.org/learn".parse().unwrap();
+///let mut body = Vec::new();
+///let response = Request::new(&uri).range(0..100).send(&mut body).unwrap();
+///
+///if response.status_code().is(|code| code == 206) {
+///    for part in range::parts(&response, &body).unwrap() {
+///        println!("{}: {} bytes", part.range, part.body.len());
+///    }
+///}
+///```
+pub fn parts(res: &Response, body: &[u8]) -> Result<Vec<Part>, ParseErr> {
+    if res.status_code() != StatusCode::new(206) {
+        return Err(ParseErr::StatusErr);
+    }
+
+    let content_type = res.headers().get("Content-Type").map_or("", String::as_str);
+    let mut params = content_type.split(';').map(str::trim);
+
+    if matches!(params.next(), Some(mime) if mime.eq_ignore_ascii_case("multipart/byteranges")) {
+        let boundary = params
+            .filter_map(|param| {
+                let mut param = param.splitn(2, '=');
+                match (param.next(), param.next()) {
+                    (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("boundary") => {
+                        Some(value.trim().trim_matches('"'))
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+            .ok_or(ParseErr::HeadersErr)?;
+
+        return multipart(boundary, body);
+    }
+
+    let range = res.content_range().ok_or(ParseErr::HeadersErr)?;
+
+    Ok(vec![Part {
+        range,
+        headers: res.headers().clone(),
+        body: body.to_vec(),
+    }])
+}
+
+//Parses `multipart/byteranges` body with parts separated by `boundary` (RFC 2046, section 5.1.1).
+fn multipart(boundary: &str, body: &[u8]) -> Result<Vec<Part>, ParseErr> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut pos = find_slice(body, &delimiter).ok_or(ParseErr::Invalid)? - delimiter.len();
+
+    loop {
+        pos += delimiter.len();
+
+        if body[pos..].starts_with(b"--") {
+            return Ok(parts);
+        }
+
+        //transport padding after the delimiter is skipped
+        pos += find_slice(&body[pos..], CR_LF).ok_or(ParseErr::Invalid)?;
+
+        let (head, start) = if body[pos..].starts_with(CR_LF) {
+            (&[][..], pos + CR_LF.len())
+        } else {
+            let end = pos + find_slice(&body[pos..], &CR_LF_2).ok_or(ParseErr::Invalid)?;
+            (&body[pos..end - CR_LF_2.len()], end)
+        };
+
+        let next = [CR_LF, &delimiter].concat();
+        let end = start + find_slice(&body[start..], &next).ok_or(ParseErr::Invalid)?;
+        let headers: Headers = str::from_utf8(head)?.parse()?;
+        let range = headers
+            .get("Content-Range")
+            .ok_or(ParseErr::HeadersErr)?
+            .parse()?;
+
+        parts.push(Part {
+            range,
+            headers,
+            body: body[start..end - next.len()].to_vec(),
+        });
+
+        pos = end - delimiter.len();
+    }
+}
+
+///Downloads body of the response to `request` to the file at `path`, resuming
+///an interrupted download if possible.
+///
+///While downloading, a strong `ETag` or `Last-Modified` of the response is stored next
+///to the file, in `<path>.validator`, and removed once the download is complete. If a download was
+///interrupted, the next one requests only the missing bytes, with `If-Range` set to
+///the stored validator, so that the whole body is sent again if it has changed.
+///
+///Body of a response with status other than `200 OK` or `206 Partial Content`
+///isn't written to the file.
+///
+///# Examples
+///```
+///use http_req::{range, request::Request, uri::Uri};
+///use std::env;
+///
+///let uri: Uri = "https://www.rust-lang.org/learn".parse().unwrap();
+///let path = env::temp_dir().join("learn.html");
+///
+///let response = range::download_to_file(&Request::new(&uri), &path).unwrap();
+///println!("Status: {}", response.status_code());
+///```
+pub fn download_to_file<P: AsRef<Path>>(
+    request: &Request,
+    path: P,
+) -> Result<Response, error::Error> {
+    let path = path.as_ref();
+    let validator_path = validator_path(path);
+    let offset = fs::metadata(path).map_or(0, |meta| meta.len());
+    let validator = match offset {
+        0 => None,
+        _ => fs::read_to_string(&validator_path).ok(),
+    };
+
+    let mut request = request.clone();
+
+    if let Some(validator) = &validator {
+        request.range(offset..).header("If-Range", validator);
+    }
+
+    let (res, mut body) = request.open()?;
+    let status: u16 = res.status_code().into();
+    let mut file = match (status, res.content_range()) {
+        (200, _) => {
+            match self::validator(&res) {
+                Some(validator) => fs::write(&validator_path, validator)?,
+                None => remove_file(&validator_path)?,
+            }
+
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?
+        }
+        (
+            206,
+            Some(ContentRange::Satisfied {
+                first,
+                last,
+                complete_length,
+            }),
+        ) if validator.is_some() => {
+            if first != offset || complete_length != Some(last + 1) {
+                return Err(ParseErr::HeadersErr.into());
+            }
+
+            OpenOptions::new().append(true).open(path)?
+        }
+        (416, Some(ContentRange::Unsatisfied(len))) if validator.is_some() && len == offset => {
+            remove_file(&validator_path)?;
+            return Ok(res);
+        }
+        _ => return Ok(res),
+    };
+
+    io::copy(&mut body, &mut file)?;
+    file.flush()?;
+    remove_file(&validator_path)?;
+
+    Ok(res)
+}
+
+//Returns path of the file storing validator of a download to `path`.
+fn validator_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".validator");
+    path.with_file_name(name)
+}
+
+//Returns a validator of `res`, which can be used in `If-Range`, as described in RFC 9110,
+//section 13.1.5: a strong `ETag` or, if there is no `ETag`, `Last-Modified` that is
+//a strong validator.
+pub(crate) fn validator(res: &Response) -> Option<String> {
+    let headers = res.headers();
+
+    if headers.get("ETag").is_some() {
+        return res
+            .etag()
+            .filter(|etag| !etag.is_weak())
+            .map(|etag| etag.to_string());
+    }
+
+    let date = headers.get("Date").and_then(|date| date::parse(date))?;
+
+    match date.duration_since(res.last_modified()?) {
+        Ok(age) if age >= STRONG_DATE_AGE => headers.get("Last-Modified").cloned(),
+        _ => None,
+    }
+}
+
+//Removes the file at `path`, if it exists.
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve;
+    use std::env;
+
+    fn response(head: &str) -> Response {
+        Response::from_head(head.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn byte_range_display() {
+        assert_eq!(ByteRange::from(0..500).to_string(), "0-499");
+        assert_eq!(ByteRange::from(10..=20).to_string(), "10-20");
+        assert_eq!(ByteRange::from(500..).to_string(), "500-");
+        assert_eq!(ByteRange::Suffix(500).to_string(), "-500");
+        assert_eq!(
+            header_value(&[ByteRange::from(0..10), ByteRange::Suffix(10)]),
+            "bytes=0-9, -10"
+        );
+    }
+
+    #[test]
+    fn content_range_from_str() {
+        const VALID: [(&str, ContentRange); 4] = [
+            (
+                "bytes 0-499/1234",
+                ContentRange::Satisfied {
+                    first: 0,
+                    last: 499,
+                    complete_length: Some(1234),
+                },
+            ),
+            (
+                "bytes 500-999/*",
+                ContentRange::Satisfied {
+                    first: 500,
+                    last: 999,
+                    complete_length: None,
+                },
+            ),
+            (
+                "Bytes  7-7/8",
+                ContentRange::Satisfied {
+                    first: 7,
+                    last: 7,
+                    complete_length: Some(8),
+                },
+            ),
+            ("bytes */1234", ContentRange::Unsatisfied(1234)),
+        ];
+        const INVALID: [&str; 7] = [
+            "bytes 5-4/10",
+            "bytes 0-10/10",
+            "bytes */*",
+            "bytes 0-/10",
+            "bytes -1-4/10",
+            "items 0-4/10",
+            "bytes 0-4",
+        ];
+
+        for (s, range) in VALID.iter() {
+            assert_eq!(s.parse::<ContentRange>(), Ok(*range));
+        }
+
+        for s in INVALID.iter() {
+            assert_eq!(s.parse::<ContentRange>(), Err(ParseErr::Invalid));
+        }
+
+        let range: ContentRange = "bytes 0-499/1234".parse().unwrap();
+        assert_eq!(range.to_string(), "bytes 0-499/1234");
+        assert_eq!(range.len(), 500);
+        assert_eq!(range.complete_length(), Some(1234));
+        assert!(ContentRange::Unsatisfied(10).is_empty());
+    }
+
+    #[test]
+    fn parts_single() {
+        let res = response("HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 2-4/10\r\n\r\n");
+        let parts = parts(&res, b"cde").unwrap();
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].range, "bytes 2-4/10".parse().unwrap());
+        assert_eq!(parts[0].body, b"cde");
+
+        let res = response("HTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(super::parts(&res, b"abc"), Err(ParseErr::StatusErr));
+
+        let res = response("HTTP/1.1 206 Partial Content\r\n\r\n");
+        assert_eq!(super::parts(&res, b"abc"), Err(ParseErr::HeadersErr));
+    }
+
+    #[test]
+    fn parts_multipart() {
+        let res = response(
+            "HTTP/1.1 206 Partial Content\r\n\
+             Content-Type: multipart/byteranges; boundary=\"THIS_SEPARATES\"\r\n\r\n",
+        );
+        let body = b"preamble\r\n\
+                     --THIS_SEPARATES\r\n\
+                     Content-Type: text/plain\r\n\
+                     Content-Range: bytes 0-4/20\r\n\r\n\
+                     ab\r\nc\r\n\
+                     --THIS_SEPARATES  \r\n\
+                     Content-Range: bytes 15-19/20\r\n\r\n\
+                     vwxyz\r\n\
+                     --THIS_SEPARATES--\r\n\
+                     epilogue";
+        let parts = parts(&res, body).unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].range, "bytes 0-4/20".parse().unwrap());
+        assert_eq!(
+            parts[0].headers.get("Content-Type"),
+            Some(&"text/plain".to_string())
+        );
+        assert_eq!(parts[0].body, b"ab\r\nc");
+        assert_eq!(parts[1].range, "bytes 15-19/20".parse().unwrap());
+        assert_eq!(parts[1].body, b"vwxyz");
+
+        assert_eq!(
+            super::parts(&res, b"--THIS_SEPARATES\r\n\r\nabc"),
+            Err(ParseErr::Invalid)
+        );
+        assert_eq!(
+            super::parts(&res, b"--THIS_SEPARATES\r\n\r\nabc\r\n--THIS_SEPARATES--"),
+            Err(ParseErr::HeadersErr)
+        );
+    }
+
+    #[test]
+    fn download_to_file_resume() {
+        let (uri, handle) = serve(
+            "/file",
+            vec![
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 10\r\n\r\nhello",
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-9/10\r\n\
+                 Content-Length: 5\r\n\r\nworld",
+                "HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nnew",
+            ],
+        );
+        let path = env::temp_dir().join(format!("http_req_download_{}", uri.corr_port()));
+        let validator_path = validator_path(&path);
+        let request = Request::new(&uri);
+
+        assert!(download_to_file(&request, &path).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"hello");
+        assert_eq!(fs::read_to_string(&validator_path).unwrap(), "\"v1\"");
+
+        let res = download_to_file(&request, &path).unwrap();
+        assert_eq!(res.status_code(), StatusCode::new(206));
+        assert_eq!(fs::read(&path).unwrap(), b"helloworld");
+        assert!(!validator_path.exists());
+
+        let res = download_to_file(&request, &path).unwrap();
+        assert_eq!(res.status_code(), StatusCode::new(200));
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        fs::remove_file(&path).unwrap();
+
+        let heads = handle.join().unwrap();
+        assert!(!heads[0].contains("Range"));
+        assert!(heads[1].contains("Range: bytes=5-\r\n"));
+        assert!(heads[1].contains("If-Range: \"v1\"\r\n"));
+        assert!(!heads[2].contains("Range"));
+    }
+
+    #[test]
+    fn download_to_file_changed() {
+        let (uri, handle) = serve(
+            "/file",
+            vec![
+                "HTTP/1.1 200 OK\r\nDate: Wed, 21 Oct 2015 07:29:00 GMT\r\n\
+                 Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\nContent-Length: 10\r\n\r\nhello",
+                "HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nchanged",
+            ],
+        );
+        let path = env::temp_dir().join(format!("http_req_download_{}", uri.corr_port()));
+        let request = Request::new(&uri);
+
+        assert!(download_to_file(&request, &path).is_err());
+        download_to_file(&request, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"changed");
+        assert!(!validator_path(&path).exists());
+        fs::remove_file(&path).unwrap();
+
+        let heads = handle.join().unwrap();
+        assert!(heads[1].contains("If-Range: Wed, 21 Oct 2015 07:28:00 GMT\r\n"));
+    }
+
+    #[test]
+    fn download_to_file_incomplete_range() {
+        let (uri, handle) = serve(
+            "/file",
+            vec![
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 10\r\n\r\nhello",
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-7/10\r\n\
+                 Content-Length: 3\r\n\r\nwor",
+            ],
+        );
+        let path = env::temp_dir().join(format!("http_req_download_{}", uri.corr_port()));
+        let request = Request::new(&uri);
+
+        assert!(download_to_file(&request, &path).is_err());
+        assert!(download_to_file(&request, &path).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"hello");
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(validator_path(&path)).unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn validator_strong() {
+        const CASES: [(&str, Option<&str>); 6] = [
+            (
+                "ETag: \"v1\"\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT",
+                Some("\"v1\""),
+            ),
+            (
+                "ETag: W/\"v1\"\r\nDate: Wed, 21 Oct 2015 08:28:00 GMT\r\n\
+              Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT",
+                None,
+            ),
+            (
+                "ETag: v1\r\nDate: Wed, 21 Oct 2015 08:28:00 GMT\r\n\
+              Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT",
+                None,
+            ),
+            (
+                "Date: Wed, 21 Oct 2015 07:29:00 GMT\r\n\
+              Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT",
+                Some("Wed, 21 Oct 2015 07:28:00 GMT"),
+            ),
+            (
+                "Date: Wed, 21 Oct 2015 07:28:59 GMT\r\n\
+              Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT",
+                None,
+            ),
+            ("Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT", None),
+        ];
+
+        for (headers, expected) in CASES.iter() {
+            let res = response(&format!("HTTP/1.1 200 OK\r\n{}\r\n\r\n", headers));
+            assert_eq!(validator(&res).as_deref(), *expected, "{}", headers);
+        }
+    }
+}
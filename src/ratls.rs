@@ -0,0 +1,442 @@
+//!verification of RA-TLS certificates presented by SGX enclaves
+//!
+//!An RA-TLS certificate is a self-signed X.509 certificate with an extension carrying
+//!an SGX quote. The quote is bound to the certificate through its report data, which
+//!starts with SHA-256 hash of the certificate's DER-encoded `SubjectPublicKeyInfo`.
+use std::prelude::v1::*;
+use crate::{
+    error::ParseErr,
+    tls::{sha256, to_hex},
+};
+use rustls::{RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+use std::{convert::TryInto, time::SystemTime};
+
+//1.2.840.113741.1.13.1
+const QUOTE_OID: [u8; 9] = [0x2A, 0x86, 0x48, 0x86, 0xF8, 0x4D, 0x01, 0x0D, 0x01];
+
+const QUOTE_HEADER_LEN: usize = 48;
+const REPORT_BODY_LEN: usize = 384;
+const ATTRIBUTES_DEBUG: u64 = 0x02;
+
+static SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+///Enclave report body, as found in an SGX quote
+#[derive(Debug, PartialEq, Clone)]
+pub struct Report {
+    attributes: u64,
+    mr_enclave: [u8; 32],
+    mr_signer: [u8; 32],
+    isv_prod_id: u16,
+    isv_svn: u16,
+    report_data: [u8; 64],
+}
+
+impl Report {
+    ///Parses report body of an SGX quote.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::ratls::Report;
+    ///
+    ///let quote = include_bytes!("../tests/certs/ratls_quote.bin");
+    ///let report = Report::from_quote(quote).unwrap();
+    ///
+    ///assert_eq!(report.isv_svn(), 3);
+    ///```
+    pub fn from_quote(quote: &[u8]) -> Result<Report, ParseErr> {
+        let body = quote
+            .get(QUOTE_HEADER_LEN..QUOTE_HEADER_LEN + REPORT_BODY_LEN)
+            .ok_or(ParseErr::Invalid)?;
+
+        let mut report = Report {
+            attributes: u64::from_le_bytes(body[48..56].try_into().unwrap()),
+            mr_enclave: [0; 32],
+            mr_signer: [0; 32],
+            isv_prod_id: u16::from_le_bytes([body[256], body[257]]),
+            isv_svn: u16::from_le_bytes([body[258], body[259]]),
+            report_data: [0; 64],
+        };
+
+        report.mr_enclave.copy_from_slice(&body[64..96]);
+        report.mr_signer.copy_from_slice(&body[128..160]);
+        report.report_data.copy_from_slice(&body[320..384]);
+
+        Ok(report)
+    }
+
+    ///Returns attribute flags of the enclave.
+    pub fn attributes(&self) -> u64 {
+        self.attributes
+    }
+
+    ///Returns measurement of the enclave (MRENCLAVE).
+    pub fn mr_enclave(&self) -> &[u8; 32] {
+        &self.mr_enclave
+    }
+
+    ///Returns hash of the enclave signer's public key (MRSIGNER).
+    pub fn mr_signer(&self) -> &[u8; 32] {
+        &self.mr_signer
+    }
+
+    ///Returns product ID of the enclave.
+    pub fn isv_prod_id(&self) -> u16 {
+        self.isv_prod_id
+    }
+
+    ///Returns security version number of the enclave.
+    pub fn isv_svn(&self) -> u16 {
+        self.isv_svn
+    }
+
+    ///Returns data provided by the enclave when the report was created.
+    pub fn report_data(&self) -> &[u8; 64] {
+        &self.report_data
+    }
+
+    ///Checks if the enclave runs in debug mode.
+    pub fn is_debug(&self) -> bool {
+        self.attributes & ATTRIBUTES_DEBUG != 0
+    }
+}
+
+///Verifies signature of an SGX quote, e.g. with DCAP quote verification library
+///or Intel Attestation Service.
+pub trait QuoteVerifier: Send + Sync {
+    ///Checks that `quote` is genuine. Called before the report body is inspected.
+    fn verify_quote(&self, quote: &[u8]) -> Result<(), TLSError>;
+}
+
+///Identity of enclaves accepted by `Verifier`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Policy {
+    mr_enclave: Option<[u8; 32]>,
+    mr_signer: Option<[u8; 32]>,
+    isv_prod_id: Option<u16>,
+    min_isv_svn: u16,
+    allow_debug: bool,
+}
+
+impl Policy {
+    ///Creates a new `Policy`. It accepts no enclave, until restricted to
+    ///an MRENCLAVE or an MRSIGNER.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::ratls::Policy;
+    ///
+    ///let mut policy = Policy::new();
+    ///policy.mr_signer([0x22; 32]).isv_prod_id(1).min_isv_svn(3);
+    ///```
+    pub fn new() -> Policy {
+        Policy::default()
+    }
+
+    ///Accepts only enclaves with given measurement.
+    pub fn mr_enclave(&mut self, mr_enclave: [u8; 32]) -> &mut Self {
+        self.mr_enclave = Some(mr_enclave);
+        self
+    }
+
+    ///Accepts only enclaves signed by the key with given hash.
+    pub fn mr_signer(&mut self, mr_signer: [u8; 32]) -> &mut Self {
+        self.mr_signer = Some(mr_signer);
+        self
+    }
+
+    ///Accepts only enclaves with given product ID.
+    pub fn isv_prod_id(&mut self, isv_prod_id: u16) -> &mut Self {
+        self.isv_prod_id = Some(isv_prod_id);
+        self
+    }
+
+    ///Accepts only enclaves with security version number at least `min_isv_svn`.
+    pub fn min_isv_svn(&mut self, min_isv_svn: u16) -> &mut Self {
+        self.min_isv_svn = min_isv_svn;
+        self
+    }
+
+    ///Sets whether enclaves running in debug mode are accepted. Disabled by default.
+    pub fn allow_debug(&mut self, allow_debug: bool) -> &mut Self {
+        self.allow_debug = allow_debug;
+        self
+    }
+
+    ///Checks if `report` satisfies this `Policy`.
+    pub fn check(&self, report: &Report) -> Result<(), TLSError> {
+        if self.mr_enclave.is_none() && self.mr_signer.is_none() {
+            return Err(ratls_error(
+                "policy restricts neither MRENCLAVE nor MRSIGNER",
+            ));
+        }
+
+        if let Some(mr_enclave) = &self.mr_enclave {
+            if mr_enclave != report.mr_enclave() {
+                return Err(ratls_error(&format!(
+                    "MRENCLAVE mismatch: {}",
+                    to_hex(report.mr_enclave())
+                )));
+            }
+        }
+
+        if let Some(mr_signer) = &self.mr_signer {
+            if mr_signer != report.mr_signer() {
+                return Err(ratls_error(&format!(
+                    "MRSIGNER mismatch: {}",
+                    to_hex(report.mr_signer())
+                )));
+            }
+        }
+
+        if let Some(isv_prod_id) = self.isv_prod_id {
+            if isv_prod_id != report.isv_prod_id() {
+                return Err(ratls_error(&format!(
+                    "ISV product ID mismatch: {}",
+                    report.isv_prod_id()
+                )));
+            }
+        }
+
+        if report.isv_svn() < self.min_isv_svn {
+            return Err(ratls_error(&format!(
+                "ISV SVN {} is lower than {}",
+                report.isv_svn(),
+                self.min_isv_svn
+            )));
+        }
+
+        if report.is_debug() && !self.allow_debug {
+            return Err(ratls_error("enclave runs in debug mode"));
+        }
+
+        Ok(())
+    }
+}
+
+///Verifier of RA-TLS certificates. Accepts a self-signed certificate if its quote
+///is genuine, satisfies the policy and is bound to the certificate's public key.
+///
+///The server's hostname is not checked, as the enclave is identified by its quote.
+pub struct Verifier {
+    policy: Policy,
+    quote_verifier: Box<dyn QuoteVerifier>,
+}
+
+impl Verifier {
+    ///Creates a new `Verifier`.
+    pub fn new<Q>(policy: Policy, quote_verifier: Q) -> Verifier
+    where
+        Q: QuoteVerifier + 'static,
+    {
+        Verifier {
+            policy,
+            quote_verifier: Box::new(quote_verifier),
+        }
+    }
+
+    ///Verifies a DER-encoded RA-TLS certificate at time `now`. Returns the report
+    ///of the enclave.
+    pub fn verify_cert(&self, cert: &[u8], now: SystemTime) -> Result<Report, TLSError> {
+        let time = webpki::Time::try_from(now).map_err(|_| TLSError::FailedToGetCurrentTime)?;
+        let anchor = webpki::trust_anchor_util::cert_der_as_trust_anchor(cert)
+            .map_err(TLSError::WebPKIError)?;
+
+        webpki::EndEntityCert::from(cert)
+            .and_then(|end_entity| {
+                end_entity.verify_is_valid_tls_server_cert(
+                    SIG_ALGS,
+                    &webpki::TLSServerTrustAnchors(&[anchor]),
+                    &[],
+                    time,
+                )
+            })
+            .map_err(TLSError::WebPKIError)?;
+
+        let quote = crate::der::extension(cert, &QUOTE_OID)
+            .map_err(|_| TLSError::WebPKIError(webpki::Error::BadDER))?
+            .ok_or_else(|| ratls_error("certificate carries no SGX quote"))?;
+
+        self.quote_verifier.verify_quote(quote)?;
+
+        let report = Report::from_quote(quote).map_err(|_| ratls_error("malformed SGX quote"))?;
+        self.policy.check(&report)?;
+
+        let spki = crate::der::subject_public_key_info(cert)
+            .map_err(|_| TLSError::WebPKIError(webpki::Error::BadDER))?;
+
+        if report.report_data()[..32] != sha256(spki) {
+            return Err(ratls_error(
+                "report data is not bound to certificate public key",
+            ));
+        }
+
+        Ok(report)
+    }
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let cert = presented_certs
+            .first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+
+        self.verify_cert(&cert.0, SystemTime::now())?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+fn ratls_error(msg: &str) -> TLSError {
+    TLSError::General(format!("RA-TLS verification failed: {}", msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const QUOTE: &[u8] = include_bytes!("../tests/certs/ratls_quote.bin");
+    const CERT_DER: &[u8] = include_bytes!("../tests/certs/ratls.der");
+    const UNBOUND_CERT_DER: &[u8] = include_bytes!("../tests/certs/ratls_unbound.der");
+    const CA_CERT_DER: &[u8] = include_bytes!("../tests/certs/ca.der");
+
+    ///Accepts only the recorded quote.
+    struct RecordedQuote;
+
+    impl QuoteVerifier for RecordedQuote {
+        fn verify_quote(&self, quote: &[u8]) -> Result<(), TLSError> {
+            if quote == QUOTE {
+                Ok(())
+            } else {
+                Err(TLSError::General("unknown quote".to_string()))
+            }
+        }
+    }
+
+    fn policy() -> Policy {
+        let mut policy = Policy::new();
+        policy
+            .mr_enclave([0x11; 32])
+            .mr_signer([0x22; 32])
+            .isv_prod_id(1)
+            .min_isv_svn(3);
+        policy
+    }
+
+    fn report() -> Report {
+        Report::from_quote(QUOTE).unwrap()
+    }
+
+    fn now() -> SystemTime {
+        SystemTime::now()
+    }
+
+    #[test]
+    fn report_from_quote() {
+        let report = report();
+
+        assert_eq!(report.attributes(), 0x05);
+        assert_eq!(report.mr_enclave(), &[0x11; 32]);
+        assert_eq!(report.mr_signer(), &[0x22; 32]);
+        assert_eq!(report.isv_prod_id(), 1);
+        assert_eq!(report.isv_svn(), 3);
+        assert_eq!(report.report_data()[32..], [0; 32]);
+        assert!(!report.is_debug());
+
+        assert_eq!(Report::from_quote(&QUOTE[..300]), Err(ParseErr::Invalid));
+    }
+
+    #[test]
+    fn policy_check() {
+        assert_eq!(policy().check(&report()), Ok(()));
+        assert!(Policy::new().check(&report()).is_err());
+        assert!(Policy::new().mr_signer([0x22; 32]).check(&report()).is_ok());
+    }
+
+    #[test]
+    fn policy_check_mismatch() {
+        assert!(policy().mr_enclave([0; 32]).check(&report()).is_err());
+        assert!(policy().mr_signer([0; 32]).check(&report()).is_err());
+        assert!(policy().isv_prod_id(2).check(&report()).is_err());
+        assert!(policy().min_isv_svn(4).check(&report()).is_err());
+    }
+
+    #[test]
+    fn policy_check_debug() {
+        let mut report = report();
+        report.attributes |= ATTRIBUTES_DEBUG;
+
+        assert!(report.is_debug());
+        assert!(policy().check(&report).is_err());
+        assert_eq!(policy().allow_debug(true).check(&report), Ok(()));
+    }
+
+    #[test]
+    fn verifier_verify_cert() {
+        let verifier = Verifier::new(policy(), RecordedQuote);
+
+        assert_eq!(verifier.verify_cert(CERT_DER, now()), Ok(report()));
+    }
+
+    #[test]
+    fn verifier_verify_cert_unbound() {
+        let verifier = Verifier::new(policy(), RecordedQuote);
+
+        match verifier.verify_cert(UNBOUND_CERT_DER, now()) {
+            Err(TLSError::General(msg)) => assert!(msg.contains("not bound")),
+            other => panic!("Expected binding error, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verifier_verify_cert_invalid() {
+        let verifier = Verifier::new(policy(), RecordedQuote);
+
+        assert!(verifier.verify_cert(CA_CERT_DER, now()).is_err());
+        assert!(verifier.verify_cert(&CERT_DER[..200], now()).is_err());
+        assert!(verifier
+            .verify_cert(CERT_DER, UNIX_EPOCH + Duration::from_secs(1_000_000_000))
+            .is_err());
+        assert!(
+            Verifier::new(policy().min_isv_svn(4).clone(), RecordedQuote)
+                .verify_cert(CERT_DER, now())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn verifier_verify_cert_quote_rejected() {
+        struct RejectAll;
+
+        impl QuoteVerifier for RejectAll {
+            fn verify_quote(&self, _quote: &[u8]) -> Result<(), TLSError> {
+                Err(TLSError::General("quote rejected".to_string()))
+            }
+        }
+
+        let verifier = Verifier::new(policy(), RejectAll);
+
+        assert_eq!(
+            verifier.verify_cert(CERT_DER, now()),
+            Err(TLSError::General("quote rejected".to_string()))
+        );
+    }
+}
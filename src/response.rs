@@ -1,7 +1,12 @@
 //! parsing server response
 use std::prelude::v1::*;
+#[cfg(any(feature = "native-tls", feature = "rust-tls"))]
+use crate::tls::TlsInfo;
 use crate::{
+    date,
     error::{Error, ParseErr},
+    etag::ETag,
+    range::ContentRange,
     uri::Uri,
 };
 use std::{
@@ -9,6 +14,7 @@ use std::{
     fmt,
     io::Write,
     str,
+    time::SystemTime,
 };
 use unicase::Ascii;
 
@@ -21,6 +27,8 @@ pub(crate) const CR_LF_2: [u8; 4] = [13, 10, 13, 10];
 pub struct Response {
     status: Status,
     headers: Headers,
+    #[cfg(any(feature = "native-tls", feature = "rust-tls"))]
+    tls_info: Option<TlsInfo>,
 }
 
 impl Response {
@@ -43,7 +51,12 @@ impl Response {
         let status = head.next().ok_or(ParseErr::StatusErr)?.parse()?;
         let headers = head.next().ok_or(ParseErr::HeadersErr)?.parse()?;
 
-        Ok(Response { status, headers })
+        Ok(Response {
+            status,
+            headers,
+            #[cfg(any(feature = "native-tls", feature = "rust-tls"))]
+            tls_info: None,
+        })
     }
 
     ///Parses `Response` from slice of bytes. Writes it's body to `writer`.
@@ -157,6 +170,11 @@ impl Response {
         &self.headers
     }
 
+    ///Returns mutable reference to headers of this `Response`.
+    pub(crate) fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
     ///Returns length of the content of this `Response` as a `Option`, according to information
     ///included in headers. If there is no such an information, returns `None`.
     ///
@@ -179,6 +197,94 @@ impl Response {
             .get("Content-Length")
             .and_then(|len| len.parse().ok())
     }
+
+    ///Returns range of bytes sent in this `Response`, parsed from `Content-Range`.
+    ///Returns `None` if the header is missing or invalid.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::{range::ContentRange, response::Response};
+    ///
+    ///const HEAD: &[u8] = b"HTTP/1.1 206 Partial Content\r\n\
+    ///                      Content-Range: bytes 0-99/1000\r\n\r\n";
+    ///
+    ///let response = Response::from_head(HEAD).unwrap();
+    ///assert_eq!(response.content_range().unwrap().len(), 100);
+    ///```
+    pub fn content_range(&self) -> Option<ContentRange> {
+        self.headers()
+            .get("Content-Range")
+            .and_then(|range| range.parse().ok())
+    }
+
+    ///Returns the entity tag of this `Response`, parsed from the `ETag` header.
+    ///Returns `None` if it's missing or invalid.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::response::Response;
+    ///
+    ///const HEAD: &[u8] = b"HTTP/1.1 200 OK\r\n\
+    ///                      ETag: W/\"xyzzy\"\r\n\r\n";
+    ///
+    ///let response = Response::from_head(HEAD).unwrap();
+    ///let etag = response.etag().unwrap();
+    ///
+    ///assert!(etag.is_weak());
+    ///assert_eq!(etag.tag(), "xyzzy");
+    ///```
+    pub fn etag(&self) -> Option<ETag> {
+        self.headers()
+            .get("ETag")
+            .and_then(|etag| etag.parse().ok())
+    }
+
+    ///Returns the time of the last modification of the resource, parsed from
+    ///the `Last-Modified` header. Returns `None` if it's missing or invalid.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::response::Response;
+    ///use std::time::{Duration, UNIX_EPOCH};
+    ///
+    ///const HEAD: &[u8] = b"HTTP/1.1 200 OK\r\n\
+    ///                      Last-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n";
+    ///
+    ///let response = Response::from_head(HEAD).unwrap();
+    ///assert_eq!(
+    ///    response.last_modified(),
+    ///    Some(UNIX_EPOCH + Duration::from_secs(784_111_777))
+    ///);
+    ///```
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.headers()
+            .get("Last-Modified")
+            .and_then(|time| date::parse(time))
+    }
+
+    ///Returns details of the TLS connection this `Response` was received over.
+    ///Returns `None` if it was not received over TLS.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::request;
+    ///
+    ///let mut writer = Vec::new();
+    ///let response = request::get("https://www.rust-lang.org/learn", &mut writer).unwrap();
+    ///
+    ///if let Some(info) = response.tls_info() {
+    ///    println!("{:?} {:?}", info.version(), info.cipher_suite());
+    ///}
+    ///```
+    #[cfg(any(feature = "native-tls", feature = "rust-tls"))]
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls_info.as_ref()
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "rust-tls"))]
+    pub(crate) fn set_tls_info(&mut self, tls_info: TlsInfo) {
+        self.tls_info = Some(tls_info);
+    }
 }
 
 ///Status of HTTP response
@@ -233,7 +339,12 @@ impl str::FromStr for Status {
 ///assert_eq!(headers.get("Connection"), Some(&"Close".to_string()))
 ///```
 #[derive(Debug, PartialEq, Clone, Default)]
-pub struct Headers(HashMap<Ascii<String>, String>);
+pub struct Headers(
+    HashMap<Ascii<String>, String>,
+    //All values of fields, which can't be combined into one, i.e. `Set-Cookie`,
+    //in the order they were parsed. The last one is also in the first map.
+    HashMap<Ascii<String>, Vec<String>>,
+);
 
 impl Headers {
     ///Creates an empty `Headers`.
@@ -248,7 +359,7 @@ impl Headers {
     ///let mut headers = Headers::new();
     ///```
     pub fn new() -> Headers {
-        Headers(HashMap::new())
+        Headers(HashMap::new(), HashMap::new())
     }
 
     ///Creates empty `Headers` with the specified capacity.
@@ -263,7 +374,7 @@ impl Headers {
     ///let mut headers = Headers::with_capacity(200);
     ///```
     pub fn with_capacity(capacity: usize) -> Headers {
-        Headers(HashMap::with_capacity(capacity))
+        Headers(HashMap::with_capacity(capacity), HashMap::new())
     }
 
     ///An iterator visiting all key-value pairs in arbitrary order.
@@ -299,6 +410,28 @@ impl Headers {
         self.0.get(&Ascii::new(k.to_string()))
     }
 
+    ///Returns references to all values corresponding to the key. Repeated fields are
+    ///combined into one value, except `Set-Cookie`, whose values are returned separately,
+    ///in the order they were received. `get` returns the last of them.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::response::Headers;
+    ///
+    ///let headers: Headers = "Set-Cookie: a=1\r\nSet-Cookie: b=2".parse().unwrap();
+    ///
+    ///assert_eq!(headers.get_all("Set-Cookie"), vec!["a=1", "b=2"]);
+    ///assert_eq!(headers.get("Set-Cookie"), Some(&"b=2".to_string()));
+    ///```
+    pub fn get_all<T: ToString + ?Sized>(&self, k: &T) -> Vec<&String> {
+        let key = Ascii::new(k.to_string());
+
+        match self.1.get(&key) {
+            Some(values) => values.iter().collect(),
+            None => self.0.get(&key).into_iter().collect(),
+        }
+    }
+
     ///Inserts a key-value pair into the headers.
     ///
     ///If the headers did not have this key present, None is returned.
@@ -318,10 +451,32 @@ impl Headers {
         T: ToString + ?Sized,
         U: ToString + ?Sized,
     {
-        self.0.insert(Ascii::new(key.to_string()), val.to_string())
+        let key = Ascii::new(key.to_string());
+        self.1.remove(&key);
+        self.0.insert(key, val.to_string())
+    }
+
+    ///Removes a key from the headers, returning the value at the key if the key
+    ///was previously in the headers.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::response::Headers;
+    ///
+    ///let mut headers = Headers::new();
+    ///headers.insert("Accept-Language", "en-US");
+    ///
+    ///assert_eq!(headers.remove("Accept-Language"), Some("en-US".to_string()));
+    ///assert_eq!(headers.get("Accept-Language"), None);
+    ///```
+    pub fn remove<T: ToString + ?Sized>(&mut self, key: &T) -> Option<String> {
+        let key = Ascii::new(key.to_string());
+        self.1.remove(&key);
+        self.0.remove(&key)
     }
 
-    ///Creates default headers for a HTTP request
+    ///Creates default headers for a HTTP request. It contains only `Host`;
+    ///`Referer` is never sent by default.
     ///
     ///# Examples
     ///```
@@ -334,7 +489,6 @@ impl Headers {
         let mut headers = Headers::with_capacity(4);
 
         headers.insert("Host", &uri.host_header().unwrap_or_default());
-        headers.insert("Referer", &uri);
 
         headers
     }
@@ -347,16 +501,38 @@ impl str::FromStr for Headers {
         let headers = s.trim();
 
         if headers.lines().all(|e| e.contains(':')) {
-            let headers = headers
-                .lines()
-                .map(|elem| {
-                    let idx = elem.find(':').unwrap();
-                    let (key, value) = elem.split_at(idx);
-                    (Ascii::new(key.to_string()), value[1..].trim().to_string())
-                })
-                .collect();
-
-            Ok(Headers(headers))
+            let mut map: HashMap<Ascii<String>, String> = HashMap::new();
+            let mut separate: HashMap<Ascii<String>, Vec<String>> = HashMap::new();
+
+            //Repeated fields are combined into a comma-separated list (RFC 7230, section 3.2.2),
+            //except `Set-Cookie`, which can't be combined.
+            for elem in headers.lines() {
+                let idx = elem.find(':').unwrap();
+                let (key, value) = elem.split_at(idx);
+                let key = Ascii::new(key.to_string());
+                let value = value[1..].trim();
+
+                if key == Ascii::new("Set-Cookie".to_string()) {
+                    separate
+                        .entry(key.clone())
+                        .or_default()
+                        .push(value.to_string());
+                    map.insert(key, value.to_string());
+                    continue;
+                }
+
+                match map.get_mut(&key) {
+                    Some(prev) => {
+                        prev.push_str(", ");
+                        prev.push_str(value);
+                    }
+                    None => {
+                        map.insert(key, value.to_string());
+                    }
+                }
+            }
+
+            Ok(Headers(map, separate))
         } else {
             Err(ParseErr::HeadersErr)
         }
@@ -365,7 +541,7 @@ impl str::FromStr for Headers {
 
 impl From<HashMap<Ascii<String>, String>> for Headers {
     fn from(map: HashMap<Ascii<String>, String>) -> Headers {
-        Headers(map)
+        Headers(map, HashMap::new())
     }
 }
 
@@ -760,7 +936,7 @@ mod tests {
 
     #[test]
     fn headers_new() {
-        assert_eq!(Headers::new(), Headers(HashMap::new()));
+        assert_eq!(Headers::new(), Headers(HashMap::new(), HashMap::new()));
     }
 
     #[test]
@@ -778,7 +954,7 @@ mod tests {
     fn headers_insert() {
         let mut headers_expect = HashMap::new();
         headers_expect.insert(Ascii::new("Connection".to_string()), "Close".to_string());
-        let headers_expect = Headers(headers_expect);
+        let headers_expect = Headers(headers_expect, HashMap::new());
 
         let mut headers = Headers::new();
         headers.insert("Connection", "Close");
@@ -786,6 +962,16 @@ mod tests {
         assert_eq!(headers_expect, headers);
     }
 
+    #[test]
+    fn headers_remove() {
+        let mut headers = Headers::new();
+        headers.insert("Connection", "Close");
+
+        assert_eq!(headers.remove("connection"), Some("Close".to_string()));
+        assert_eq!(headers.remove("Connection"), None);
+        assert_eq!(headers, Headers::new());
+    }
+
     #[test]
     fn headers_default_http() {
         let uri = "http://doc.rust-lang.org/std/string/index.html"
@@ -794,7 +980,6 @@ mod tests {
 
         let mut headers = Headers::with_capacity(4);
         headers.insert("Host", "doc.rust-lang.org");
-        headers.insert("Referer", "http://doc.rust-lang.org/std/string/index.html");
 
         assert_eq!(Headers::default_http(&uri), headers);
     }
@@ -816,6 +1001,26 @@ mod tests {
         assert_eq!(headers, Headers::from(headers_expect));
     }
 
+    #[test]
+    fn headers_from_str_repeated() {
+        let headers = "Vary: Accept\r\nvary: Origin\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2"
+            .parse::<Headers>()
+            .unwrap();
+
+        assert_eq!(headers.get("Vary"), Some(&"Accept, Origin".to_string()));
+        assert_eq!(headers.get("Set-Cookie"), Some(&"b=2".to_string()));
+        assert_eq!(headers.get_all("set-cookie"), vec!["a=1", "b=2"]);
+        assert_eq!(headers.get_all("Vary"), vec!["Accept, Origin"]);
+        assert!(headers.get_all("Date").is_empty());
+
+        let mut headers = headers;
+        headers.insert("Set-Cookie", "c=3");
+        assert_eq!(headers.get_all("Set-Cookie"), vec!["c=3"]);
+
+        headers.remove("Set-Cookie");
+        assert!(headers.get_all("Set-Cookie").is_empty());
+    }
+
     #[test]
     fn headers_from() {
         let mut headers_expect = HashMap::with_capacity(4);
@@ -830,7 +1035,7 @@ mod tests {
         headers_expect.insert(Ascii::new("Content-Length".to_string()), "100".to_string());
 
         assert_eq!(
-            Headers(headers_expect.clone()),
+            Headers(headers_expect.clone(), HashMap::new()),
             Headers::from(headers_expect)
         );
     }
@@ -952,6 +1157,34 @@ mod tests {
         assert_eq!(res.content_len(), Some(100));
     }
 
+    #[test]
+    fn res_validators() {
+        const HEAD: &[u8] = b"HTTP/1.1 200 OK\r\n\
+                              ETag: \"v1\"\r\n\
+                              Last-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n";
+
+        let res = Response::from_head(HEAD).unwrap();
+        assert_eq!(res.etag(), Some(ETag::strong("v1").unwrap()));
+        assert_eq!(
+            res.last_modified().map(date::format),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT".to_string())
+        );
+
+        let res = Response::from_head(b"HTTP/1.1 200 OK\r\nETag: v1\r\nLast-Modified: 0\r\n\r\n")
+            .unwrap();
+        assert_eq!(res.etag(), None);
+        assert_eq!(res.last_modified(), None);
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "rust-tls"))]
+    #[test]
+    fn res_tls_info() {
+        let mut writer = Vec::new();
+        let res = Response::try_from(RESPONSE, &mut writer).unwrap();
+
+        assert_eq!(res.tls_info(), None);
+    }
+
     #[test]
     fn res_body() {
         {
@@ -0,0 +1,485 @@
+//! Server-Sent Events client, reading `text/event-stream` responses
+use std::prelude::v1::*;
+use crate::{
+    error::{self, ParseErr},
+    request::{BodyReader, OwnedRequest},
+    response::StatusCode,
+};
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read},
+    thread,
+    time::Duration,
+};
+
+const BOM: &str = "\u{FEFF}";
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+///Event received from a `text/event-stream`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Event {
+    ///Type of the event, `message` unless it's set by the `event` field
+    pub event: String,
+    ///Data of the event: values of its `data` fields, joined with new lines
+    pub data: String,
+    ///Last event ID set by an `id` field, in this event or before it
+    pub id: String,
+    ///Reconnection time set by a `retry` field of this event
+    pub retry: Option<Duration>,
+}
+
+///Parser of a `text/event-stream`, yielding events read from the underlying reader
+///
+///Lines may end with CRLF, LF or CR. An event, which isn't ended with a blank line
+///before the end of the stream, is discarded.
+///
+///# Examples
+///```
+///use http_req::sse::EventReader;
+///
+///let stream = "event: greeting\ndata: hello\ndata: world\n\n";
+///let mut events = EventReader::new(stream.as_bytes());
+///
+///let event = events.next().unwrap().unwrap();
+///assert_eq!(event.event, "greeting");
+///assert_eq!(event.data, "hello\nworld");
+///```
+pub struct EventReader<R> {
+    reader: BufReader<R>,
+    first_line: bool,
+    skip_lf: bool,
+    last_event_id: String,
+    retry: Option<Duration>,
+}
+
+impl<R: Read> EventReader<R> {
+    ///Creates a new `EventReader` reading from `reader`.
+    pub fn new(reader: R) -> EventReader<R> {
+        EventReader {
+            reader: BufReader::new(reader),
+            first_line: true,
+            skip_lf: false,
+            last_event_id: String::new(),
+            retry: None,
+        }
+    }
+
+    ///Returns the last event ID, set by an `id` field.
+    pub fn last_event_id(&self) -> &str {
+        &self.last_event_id
+    }
+
+    ///Returns the last reconnection time, set by a `retry` field.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    //Reads the next event, or returns `None` at the end of the stream.
+    fn read_event(&mut self) -> io::Result<Option<Event>> {
+        let mut event = String::new();
+        let mut data = String::new();
+        let mut retry = None;
+
+        while let Some(line) = self.read_line()? {
+            if line.is_empty() {
+                if data.is_empty() {
+                    event.clear();
+                    retry = None;
+                    continue;
+                }
+
+                data.pop();
+
+                if event.is_empty() {
+                    event.push_str("message");
+                }
+
+                return Ok(Some(Event {
+                    event,
+                    data,
+                    id: self.last_event_id.clone(),
+                    retry,
+                }));
+            }
+
+            let (field, value) = match line.find(':') {
+                Some(0) => continue,
+                Some(i) => {
+                    let value = &line[i + 1..];
+                    (&line[..i], value.strip_prefix(' ').unwrap_or(value))
+                }
+                None => (&line[..], ""),
+            };
+
+            match field {
+                "event" => event = value.to_string(),
+                "data" => {
+                    data.push_str(value);
+                    data.push('\n');
+                }
+                "id" if !value.contains('\0') => self.last_event_id = value.to_string(),
+                "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                    if let Ok(millis) = value.parse() {
+                        retry = Some(Duration::from_millis(millis));
+                        self.retry = retry;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    //Reads a line ended with CRLF, LF or CR, without the ending. Returns `None`
+    //at the end of the stream, discarding an unfinished line.
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+
+        loop {
+            let buf = self.reader.fill_buf()?;
+
+            if buf.is_empty() {
+                return Ok(None);
+            }
+
+            if self.skip_lf {
+                self.skip_lf = false;
+
+                if buf[0] == b'\n' {
+                    self.reader.consume(1);
+                    continue;
+                }
+            }
+
+            match buf.iter().position(|b| *b == b'\r' || *b == b'\n') {
+                Some(i) => {
+                    line.extend_from_slice(&buf[..i]);
+                    self.skip_lf = buf[i] == b'\r';
+                    self.reader.consume(i + 1);
+                    break;
+                }
+                None => {
+                    let len = buf.len();
+                    line.extend_from_slice(buf);
+                    self.reader.consume(len);
+                }
+            }
+        }
+
+        let mut line = String::from_utf8_lossy(&line).into_owned();
+
+        if self.first_line {
+            self.first_line = false;
+
+            if line.starts_with(BOM) {
+                line.drain(..BOM.len());
+            }
+        }
+
+        Ok(Some(line))
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_event().transpose()
+    }
+}
+
+///Client of a Server-Sent Events stream, which reconnects when the connection is lost
+///
+///Every connection is made with a copy of the request, with `Accept: text/event-stream`
+///and, once an event ID is received, `Last-Event-ID` headers. Before reconnecting,
+///it waits for the time set by the server with `retry`, 3 seconds by default.
+///
+///The stream is closed, and no longer reconnected, if the server responds with status
+///other than `200 OK` or with content type other than `text/event-stream`.
+///
+///# Examples
+///```
+///use http_req::{request::OwnedRequest, sse::EventSource};
+///
+///let request = OwnedRequest::new("http://localhost:8080/events".parse().unwrap());
+///let events = EventSource::new(request);
+///
+///for event in events.take(3) {
+///    match event {
+///        Ok(event) => println!("{}: {}", event.event, event.data),
+///        Err(e) => println!("error: {}", e),
+///    }
+///}
+///```
+pub struct EventSource {
+    request: OwnedRequest,
+    reader: Option<EventReader<BodyReader>>,
+    last_event_id: String,
+    retry: Duration,
+    reconnecting: bool,
+    closed: bool,
+}
+
+impl EventSource {
+    ///Creates a new `EventSource`, which connects with `request` on the first read.
+    pub fn new(request: OwnedRequest) -> EventSource {
+        EventSource {
+            request,
+            reader: None,
+            last_event_id: String::new(),
+            retry: DEFAULT_RETRY,
+            reconnecting: false,
+            closed: false,
+        }
+    }
+
+    ///Returns the last event ID, sent with `Last-Event-ID` when reconnecting.
+    pub fn last_event_id(&self) -> &str {
+        &self.last_event_id
+    }
+
+    ///Returns the current reconnection time.
+    pub fn retry(&self) -> Duration {
+        self.retry
+    }
+
+    ///Checks if the stream was closed because of the response of the server.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    ///Reads the next event, connecting or reconnecting to the server if needed.
+    ///
+    ///When the connection is lost, it reconnects once. If that fails, the error is returned
+    ///and the next call tries again. Fails with `NotConnected` once the stream is closed.
+    pub fn next_event(&mut self) -> Result<Event, error::Error> {
+        loop {
+            if self.closed {
+                return Err(
+                    io::Error::new(ErrorKind::NotConnected, "event stream is closed").into(),
+                );
+            }
+
+            let reader = match self.reader.as_mut() {
+                Some(reader) => reader,
+                None => {
+                    if self.reconnecting {
+                        thread::sleep(self.retry);
+                    }
+
+                    self.reconnecting = true;
+                    self.reader = Some(self.connect()?);
+                    self.reconnecting = false;
+                    continue;
+                }
+            };
+
+            let event = reader.next();
+
+            if let Some(retry) = reader.retry() {
+                self.retry = retry;
+            }
+
+            self.last_event_id = reader.last_event_id().to_string();
+
+            match event {
+                Some(Ok(event)) => return Ok(event),
+                Some(Err(_)) | None => {
+                    self.reader = None;
+                    self.reconnecting = true;
+                }
+            }
+        }
+    }
+
+    //Opens a new connection and checks the response of the server.
+    fn connect(&mut self) -> Result<EventReader<BodyReader>, error::Error> {
+        let mut request = self.request.clone();
+        request
+            .header("Accept", "text/event-stream")
+            .header("Cache-Control", "no-cache");
+
+        if !self.last_event_id.is_empty() {
+            request.header("Last-Event-ID", &self.last_event_id);
+        }
+
+        let (res, body) = request.as_request().open()?;
+
+        if res.status_code() != StatusCode::new(200) {
+            self.closed = true;
+
+            return Err(io::Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("event stream refused: {}", res.status_code()),
+            )
+            .into());
+        }
+
+        let content_type = res.headers().get("Content-Type").map_or("", String::as_str);
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+
+        if !mime.eq_ignore_ascii_case("text/event-stream") {
+            self.closed = true;
+            return Err(ParseErr::HeadersErr.into());
+        }
+
+        let mut reader = EventReader::new(body);
+        reader.last_event_id = self.last_event_id.clone();
+
+        Ok(reader)
+    }
+}
+
+impl Iterator for EventSource {
+    type Item = Result<Event, error::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.closed {
+            None
+        } else {
+            Some(self.next_event())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve;
+    use std::time::Instant;
+
+    fn events(stream: &str) -> Vec<Event> {
+        EventReader::new(stream.as_bytes())
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    fn event(event: &str, data: &str, id: &str) -> Event {
+        Event {
+            event: event.to_string(),
+            data: data.to_string(),
+            id: id.to_string(),
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn event_reader_fields() {
+        let stream = ": comment\n\
+                      event: add\n\
+                      data: 1\n\
+                      data:2\n\
+                      data\n\
+                      id: 7\n\
+                      unknown: field\n\n\
+                      data:  spaced\n\n";
+
+        assert_eq!(
+            events(stream),
+            vec![
+                event("add", "1\n2\n", "7"),
+                event("message", " spaced", "7")
+            ]
+        );
+    }
+
+    #[test]
+    fn event_reader_line_endings() {
+        let stream = "\u{FEFF}data: a\r\n\r\ndata: b\r\rdata: c\n\n";
+        let expected = vec![
+            event("message", "a", ""),
+            event("message", "b", ""),
+            event("message", "c", ""),
+        ];
+
+        assert_eq!(events(stream), expected);
+
+        let bytes = stream.as_bytes();
+        for split in 1..bytes.len() {
+            let reader = (&bytes[..split]).chain(&bytes[split..]);
+            let parsed: Vec<Event> = EventReader::new(reader).map(Result::unwrap).collect();
+            assert_eq!(parsed, expected);
+        }
+    }
+
+    #[test]
+    fn event_reader_dispatch() {
+        let stream = "event: ignored\n\n\
+                      id: 1\nretry: 100\ndata: a\n\n\
+                      retry: 2x\nid: \0\ndata: b\n\n\
+                      id\nretry: 200\n\n\
+                      data: c\n\n\
+                      data: unfinished\n";
+        let mut reader = EventReader::new(stream.as_bytes());
+
+        let mut a = event("message", "a", "1");
+        a.retry = Some(Duration::from_millis(100));
+
+        assert_eq!(reader.next().unwrap().unwrap(), a);
+        assert_eq!(reader.next().unwrap().unwrap(), event("message", "b", "1"));
+        assert_eq!(reader.next().unwrap().unwrap(), event("message", "c", ""));
+        assert_eq!(reader.retry(), Some(Duration::from_millis(200)));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn event_source_reconnect() {
+        let (uri, handle) = serve(
+            "/events",
+            vec![
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                 Transfer-Encoding: chunked\r\n\r\n\
+                 17\r\nretry: 10\nid: 1\ndata: a\r\n\
+                 2\r\n\n\n\r\n\
+                 0\r\n\r\n",
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream; charset=utf-8\r\n\r\n\
+                 data: b\n\n",
+            ],
+        );
+        let mut events = EventSource::new(OwnedRequest::new(uri));
+
+        let mut a = event("message", "a", "1");
+        a.retry = Some(Duration::from_millis(10));
+        assert_eq!(events.next_event().unwrap(), a);
+        assert_eq!(events.retry(), Duration::from_millis(10));
+
+        let start = Instant::now();
+        assert_eq!(events.next_event().unwrap(), event("message", "b", "1"));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert_eq!(events.last_event_id(), "1");
+
+        let heads = handle.join().unwrap();
+        assert!(heads[0].contains("Accept: text/event-stream\r\n"));
+        assert!(!heads[0].contains("Last-Event-ID"));
+        assert!(heads[1].contains("Last-Event-ID: 1\r\n"));
+
+        assert!(events.next_event().is_err());
+        assert!(!events.is_closed());
+    }
+
+    #[test]
+    fn event_source_closed() {
+        let (uri, handle) = serve(
+            "/events",
+            vec![
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 0\r\n\r\n",
+                "HTTP/1.1 204 No Content\r\n\r\n",
+            ],
+        );
+
+        for _ in 0..2 {
+            let mut events = EventSource::new(OwnedRequest::new(uri.clone()));
+
+            assert!(events.next().unwrap().is_err());
+            assert!(events.is_closed());
+            assert!(events.next().is_none());
+
+            match events.next_event() {
+                Err(error::Error::IO(e)) => assert_eq!(e.kind(), ErrorKind::NotConnected),
+                _ => panic!("event stream should be closed"),
+            }
+        }
+
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+//! localhost servers used by tests
+use std::prelude::v1::*;
+use crate::{
+    response::{find_slice, CR_LF_2},
+    uri::Uri,
+};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+};
+
+///Reads head of a request from `stream`, leaving its body unread.
+pub fn read_head<T: Read>(stream: &mut T) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0; 1];
+
+    while find_slice(&head, &CR_LF_2).is_none() {
+        stream.read_exact(&mut byte)?;
+        head.push(byte[0]);
+    }
+
+    String::from_utf8(head).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+///Reads a request from `stream`: its head and a body of `Content-Length` bytes, if any.
+pub fn read_request<T: Read>(stream: &mut T) -> io::Result<String> {
+    let mut request = read_head(stream)?;
+    let body_len = request
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Length: "))
+        .map_or(0, |len| len.parse().unwrap());
+    let mut body = vec![0; body_len];
+
+    stream.read_exact(&mut body)?;
+    request += &String::from_utf8_lossy(&body);
+    Ok(request)
+}
+
+///Starts a server on localhost, which answers consecutive connections with `responses`.
+///Returns uri of the server with `path` and the requests it received.
+pub fn serve<T>(path: &str, responses: Vec<T>) -> (Uri, JoinHandle<Vec<String>>)
+where
+    T: AsRef<[u8]> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!("http://{}{}", listener.local_addr().unwrap(), path);
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            requests.push(read_request(&mut stream).unwrap());
+            stream.write_all(response.as_ref()).unwrap();
+        }
+
+        requests
+    });
+
+    (uri.parse().unwrap(), handle)
+}
+
+///Starts a server on localhost, which accepts a single connection and passes it
+///to `handler`. Returns uri of the server with `path` and the result of `handler`.
+pub fn serve_with<F, T>(path: &str, handler: F) -> (Uri, JoinHandle<T>)
+where
+    F: FnOnce(TcpStream) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!("http://{}{}", listener.local_addr().unwrap(), path);
+    let handle = thread::spawn(move || handler(listener.accept().unwrap().0));
+
+    (uri.parse().unwrap(), handle)
+}
+
+///Returns configuration of a TLS server for `localhost`, which requires clients
+///to authenticate with certificates issued by `tests/certs/ca.pem`.
+#[cfg(feature = "rust-tls")]
+pub fn client_auth_config() -> rustls::ServerConfig {
+    use rustls::internal::pemfile;
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add_pem_file(&mut &include_bytes!("../tests/certs/ca.pem")[..])
+        .unwrap();
+
+    let certs = pemfile::certs(&mut &include_bytes!("../tests/certs/server.pem")[..]).unwrap();
+    let key = pemfile::pkcs8_private_keys(&mut &include_bytes!("../tests/certs/server.key")[..])
+        .unwrap()
+        .remove(0);
+    let mut config = rustls::ServerConfig::new(rustls::AllowAnyAuthenticatedClient::new(roots));
+    config.set_single_cert(certs, key).unwrap();
+    config
+}
+
+///Starts a TLS server on localhost with `config`, which answers a single connection
+///with `response`. Returns uri of the server with `path` and the request it received,
+///or the error which ended the connection.
+#[cfg(feature = "rust-tls")]
+pub fn serve_tls(
+    path: &str,
+    config: rustls::ServerConfig,
+    response: &'static [u8],
+) -> (Uri, JoinHandle<io::Result<String>>) {
+    let config = std::sync::Arc::new(config);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!(
+        "https://localhost:{}{}",
+        listener.local_addr().unwrap().port(),
+        path
+    );
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let session = rustls::ServerSession::new(&config);
+        let mut stream = rustls::StreamOwned::new(session, stream);
+        let request = read_request(&mut stream)?;
+
+        stream.write_all(response)?;
+        stream.flush()?;
+        Ok(request)
+    });
+
+    (uri.parse().unwrap(), handle)
+}
@@ -2,20 +2,27 @@
 
 use std::prelude::v1::*;
 use crate::error::Error as HttpError;
-use std::{
-    untrusted::fs::File,
-    io::{self, BufReader},
-    path::Path,
-};
+#[cfg(not(feature = "mesalock_sgx"))]
+use std::fs::File;
+#[cfg(feature = "mesalock_sgx")]
+use std::untrusted::fs::File;
+use std::{io, path::Path};
 
-#[cfg(feature = "native-tls")]
 use std::io::prelude::*;
 
-#[cfg(feature = "rust-tls")]
 use crate::error::ParseErr;
 
-//#[cfg(not(any(feature = "native-tls", feature = "rust-tls")))]
-//compile_error!("one of the `native-tls` or `rust-tls` features must be enabled");
+#[cfg(feature = "rust-tls")]
+use rustls::{RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+#[cfg(feature = "rust-tls")]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+//Number of sessions cached by default, as in `rustls::ClientConfig::new`.
+#[cfg(feature = "rust-tls")]
+const SESSION_CACHE_CAPACITY: usize = 32;
 
 ///wrapper around TLS Stream,
 ///depends on selected TLS library
@@ -25,28 +32,177 @@ pub struct Conn<S: io::Read + io::Write> {
 
     #[cfg(feature = "rust-tls")]
     stream: rustls::StreamOwned<rustls::ClientSession, S>,
+    #[cfg(feature = "rust-tls")]
+    verified: Arc<AtomicBool>,
+    #[cfg(feature = "rust-tls")]
+    close_notify: bool,
+}
+
+impl<S: io::Read + io::Write> Conn<S> {
+    ///Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        #[cfg(feature = "native-tls")]
+        return self.stream.get_ref();
+        #[cfg(feature = "rust-tls")]
+        return &self.stream.sock;
+    }
+
+    ///Returns the application protocol negotiated with ALPN, if any.
+    #[cfg(feature = "rust-tls")]
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        use rustls::Session;
+        self.stream.sess.get_alpn_protocol()
+    }
+
+    ///Returns the negotiated version of the TLS protocol. Returns `None` until
+    ///the handshake completes.
+    #[cfg(feature = "rust-tls")]
+    pub fn protocol_version(&self) -> Option<Version> {
+        use rustls::Session;
+        self.stream
+            .sess
+            .get_protocol_version()
+            .and_then(Version::from_rustls)
+    }
+
+    ///Returns the negotiated cipher suite. Returns `None` until the handshake completes.
+    #[cfg(feature = "rust-tls")]
+    pub fn cipher_suite(&self) -> Option<rustls::CipherSuite> {
+        use rustls::Session;
+        self.stream
+            .sess
+            .get_negotiated_ciphersuite()
+            .map(|suite| suite.suite)
+    }
+
+    ///Returns DER-encoded certificate chain presented by the server,
+    ///starting with its end-entity certificate.
+    #[cfg(feature = "rust-tls")]
+    pub fn peer_certificates(&self) -> Vec<Vec<u8>> {
+        use rustls::Session;
+        self.stream
+            .sess
+            .get_peer_certificates()
+            .map(|certs| certs.into_iter().map(|cert| cert.0).collect())
+            .unwrap_or_default()
+    }
+
+    ///Returns details of this connection.
+    #[cfg(feature = "rust-tls")]
+    pub fn tls_info(&self) -> TlsInfo {
+        TlsInfo {
+            version: self.protocol_version(),
+            cipher_suite: self.cipher_suite().map(|suite| format!("{:?}", suite)),
+            alpn_protocol: self.alpn_protocol().map(|p| p.to_vec()),
+            peer_certificates: self.peer_certificates(),
+            resumed: self.is_resumed(),
+        }
+    }
+
+    ///Returns details of this connection.
+    #[cfg(feature = "native-tls")]
+    pub fn tls_info(&self) -> TlsInfo {
+        let peer_certificate = self
+            .stream
+            .peer_certificate()
+            .ok()
+            .and_then(|cert| cert)
+            .and_then(|cert| cert.to_der().ok());
+
+        TlsInfo {
+            version: None,
+            cipher_suite: None,
+            alpn_protocol: None,
+            peer_certificates: peer_certificate.into_iter().collect(),
+            resumed: false,
+        }
+    }
+
+    ///Checks if the server closed the connection with `close_notify`. If the connection
+    ///ended without it, the data read may have been truncated by an attacker.
+    #[cfg(feature = "rust-tls")]
+    pub fn is_closed(&self) -> bool {
+        self.close_notify
+    }
+
+    ///Sends `close_notify` to the server, signaling that no more data will be written.
+    ///The connection can still be read from.
+    #[cfg(feature = "rust-tls")]
+    pub fn shutdown(&mut self) -> Result<(), io::Error> {
+        use rustls::Session;
+
+        self.stream.sess.send_close_notify();
+        self.stream.flush()
+    }
+
+    ///Sends `close_notify` to the server, signaling that no more data will be written.
+    #[cfg(feature = "native-tls")]
+    pub fn shutdown(&mut self) -> Result<(), io::Error> {
+        self.stream.shutdown()
+    }
+
+    ///Checks if the TLS session was resumed, i.e. the handshake completed without
+    ///the server presenting its certificate chain again.
+    #[cfg(feature = "rust-tls")]
+    pub fn is_resumed(&self) -> bool {
+        use rustls::Session;
+        !self.stream.sess.is_handshaking() && !self.verified.load(Ordering::SeqCst)
+    }
 }
 
+///Reads decrypted data.
+///
+///Returns `Ok(0)` once the connection ends, whether the server closed it with `close_notify`
+///or not. With `rust-tls`, `is_closed` tells these cases apart, so that data, which end
+///is delimited only by closing the connection, can be checked for truncation.
+///`native-tls` doesn't report it, so there is no equivalent check.
 impl<S: io::Read + io::Write> io::Read for Conn<S> {
+    #[cfg(feature = "native-tls")]
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
-        let len = self.stream.read(buf);
+        self.stream.read(buf)
+    }
 
-        #[cfg(feature = "rust-tls")]
-        {
-            // TODO: this api returns ConnectionAborted with a "..CloseNotify.." string.
-            // TODO: we should work out if self.stream.sess exposes enough information
-            // TODO: to not read in this situation, and return EOF directly.
-            // TODO: c.f. the checks in the implementation. connection_at_eof() doesn't
-            // TODO: seem to be exposed. The implementation:
-            // TODO: https://github.com/ctz/rustls/blob/f93c325ce58f2f1e02f09bcae6c48ad3f7bde542/src/session.rs#L789-L792
-            if let Err(ref e) = len {
-                if io::ErrorKind::ConnectionAborted == e.kind() {
+    #[cfg(feature = "rust-tls")]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        use rustls::Session;
+
+        if self.close_notify {
+            return Ok(0);
+        }
+
+        let sess = &mut self.stream.sess;
+        let sock = &mut self.stream.sock;
+
+        if sess.is_handshaking() {
+            sess.complete_io(sock)?;
+        }
+
+        loop {
+            while sess.wants_write() {
+                sess.write_tls(sock)?;
+            }
+
+            // Errors of the socket are returned by `read_tls`, so `ConnectionAborted`
+            // returned by the session always means that `close_notify` was received.
+            match sess.read(buf) {
+                Ok(0) if !buf.is_empty() => {}
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => {
+                    self.close_notify = true;
                     return Ok(0);
                 }
+                res => return res,
+            }
+
+            if sess.read_tls(sock)? == 0 {
+                return Ok(0);
             }
-        }
 
-        len
+            if let Err(e) = sess.process_new_packets() {
+                // try to send an alert to the server
+                let _ = sess.write_tls(sock);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        }
     }
 }
 
@@ -60,11 +216,37 @@ impl<S: io::Read + io::Write> io::Write for Conn<S> {
 }
 
 ///client configuration
+///
+///Clones of a `Config` share the cache of TLS sessions, so connections made
+///with any of them may resume sessions established by the others. Changing how
+///the server is verified, e.g. its trusted roots, pins or verifier, or the client
+///certificate gives the `Config` a new, empty cache, as a resumed session isn't
+///verified again.
+#[derive(Clone)]
 pub struct Config {
     #[cfg(feature = "native-tls")]
     extra_root_certs: Vec<native_tls::Certificate>,
+    #[cfg(feature = "native-tls")]
+    built_in_roots: bool,
+    #[cfg(feature = "native-tls")]
+    identity: Option<native_tls::Identity>,
+    #[cfg(feature = "native-tls")]
+    sni: bool,
     #[cfg(feature = "rust-tls")]
     client_config: std::sync::Arc<rustls::ClientConfig>,
+    #[cfg(feature = "rust-tls")]
+    pins: Vec<Pin>,
+    #[cfg(feature = "rust-tls")]
+    pins_only: bool,
+    #[cfg(feature = "rust-tls")]
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+    #[cfg(feature = "rust-tls")]
+    session_capacity: usize,
+    #[cfg(feature = "rust-tls")]
+    connector: Arc<Connector>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    server_name: Option<String>,
 }
 
 impl Default for Config {
@@ -72,6 +254,12 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             extra_root_certs: vec![],
+            built_in_roots: true,
+            identity: None,
+            sni: true,
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            server_name: None,
         }
     }
 
@@ -82,19 +270,35 @@ impl Default for Config {
             .root_store
             .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
 
-        Config {
+        let mut config = Config {
             client_config: std::sync::Arc::new(config),
-        }
+            pins: Vec::new(),
+            pins_only: false,
+            verifier: None,
+            session_capacity: SESSION_CACHE_CAPACITY,
+            connector: Arc::new(Connector::default()),
+            accept_invalid_certs: false,
+            accept_invalid_hostnames: false,
+            server_name: None,
+        };
+        config.set_session_cache(SESSION_CACHE_CAPACITY);
+        config
     }
 }
 
 impl Config {
+    ///Adds the PEM-encoded certificates from `file_path` to the trusted root store.
     #[cfg(feature = "native-tls")]
     pub fn add_root_cert_file_pem(&mut self, file_path: &Path) -> Result<&mut Self, HttpError> {
-        let f = File::open(file_path)?;
-        let f = BufReader::new(f);
+        self.add_root_cert_pem(&read_file(file_path)?)
+    }
+
+    ///Adds PEM-encoded certificates to the trusted root store.
+    #[cfg(feature = "native-tls")]
+    pub fn add_root_cert_pem(&mut self, pem: &[u8]) -> Result<&mut Self, HttpError> {
         let mut pem_crt = vec![];
-        for line in f.lines() {
+        let mut added = 0;
+        for line in pem.lines() {
             let line = line?;
             let is_end_cert = line.contains("-----END");
             pem_crt.append(&mut line.into_bytes());
@@ -103,11 +307,93 @@ impl Config {
                 let crt = native_tls::Certificate::from_pem(&pem_crt)?;
                 self.extra_root_certs.push(crt);
                 pem_crt.clear();
+                added += 1;
             }
         }
+
+        if added == 0 {
+            return Err(HttpError::from(ParseErr::Empty));
+        }
+        Ok(self)
+    }
+
+    ///Adds a DER-encoded certificate to the trusted root store.
+    #[cfg(feature = "native-tls")]
+    pub fn add_root_cert_der(&mut self, der: &[u8]) -> Result<&mut Self, HttpError> {
+        let crt = native_tls::Certificate::from_der(der)?;
+        self.extra_root_certs.push(crt);
+        Ok(self)
+    }
+
+    ///Removes all certificates from the trusted root store, including the built-in ones.
+    ///
+    ///Only certificates added afterwards will be trusted.
+    #[cfg(feature = "native-tls")]
+    pub fn clear_root_certs(&mut self) -> &mut Self {
+        self.extra_root_certs.clear();
+        self.built_in_roots = false;
+        self
+    }
+
+    ///Sets the client certificate chain and its PKCS#8 private key, both PEM-encoded,
+    ///presented to servers that request client authentication.
+    #[cfg(feature = "native-tls")]
+    pub fn set_client_cert_pem(
+        &mut self,
+        cert_chain: &[u8],
+        key: &[u8],
+    ) -> Result<&mut Self, HttpError> {
+        self.identity = Some(native_tls::Identity::from_pkcs8(cert_chain, key)?);
         Ok(self)
     }
 
+    ///Reads the client certificate chain and its PKCS#8 private key from PEM files.
+    #[cfg(feature = "native-tls")]
+    pub fn set_client_cert_file_pem(
+        &mut self,
+        cert_path: &Path,
+        key_path: &Path,
+    ) -> Result<&mut Self, HttpError> {
+        let cert_chain = read_file(cert_path)?;
+        let key = read_file(key_path)?;
+        self.set_client_cert_pem(&cert_chain, &key)
+    }
+
+    ///Sets whether the server's certificate is accepted without any verification.
+    ///
+    ///**Warning**: any certificate, including expired and self-signed ones, is then trusted,
+    ///which makes the connection open to man-in-the-middle attacks. Use only for testing.
+    #[cfg(feature = "native-tls")]
+    pub fn danger_accept_invalid_certs(&mut self, accept: bool) -> &mut Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    ///Sets whether the server's certificate is accepted even if it is not valid
+    ///for the hostname the connection is made to.
+    ///
+    ///**Warning**: any certificate issued by a trusted root is then accepted for any server,
+    ///which makes the connection open to man-in-the-middle attacks. Use only for testing.
+    #[cfg(feature = "native-tls")]
+    pub fn danger_accept_invalid_hostnames(&mut self, accept: bool) -> &mut Self {
+        self.accept_invalid_hostnames = accept;
+        self
+    }
+
+    ///Sets the name, which is sent with the SNI extension and checked against
+    ///the server's certificate, instead of the hostname passed to `connect`.
+    pub fn set_server_name<T: AsRef<str>>(&mut self, server_name: T) -> &mut Self {
+        self.server_name = Some(server_name.as_ref().to_string());
+        self
+    }
+
+    ///Sets whether the server name is sent with the SNI extension. Enabled by default.
+    #[cfg(feature = "native-tls")]
+    pub fn set_sni(&mut self, enabled: bool) -> &mut Self {
+        self.sni = enabled;
+        self
+    }
+
     #[cfg(feature = "native-tls")]
     pub fn connect<H, S>(&self, hostname: H, stream: S) -> Result<Conn<S>, HttpError>
     where
@@ -115,25 +401,387 @@ impl Config {
         S: io::Read + io::Write,
     {
         let mut connector_builder = native_tls::TlsConnector::builder();
+        connector_builder.disable_built_in_roots(!self.built_in_roots);
         for crt in self.extra_root_certs.iter() {
             connector_builder.add_root_certificate((*crt).clone());
         }
+        if let Some(identity) = &self.identity {
+            connector_builder.identity(identity.clone());
+        }
+        connector_builder.danger_accept_invalid_certs(self.accept_invalid_certs);
+        connector_builder.danger_accept_invalid_hostnames(self.accept_invalid_hostnames);
+        connector_builder.use_sni(self.sni);
         let connector = connector_builder.build()?;
-        let stream = connector.connect(hostname.as_ref(), stream)?;
+        let server_name = self.server_name.as_deref().unwrap_or(hostname.as_ref());
+        let stream = connector.connect(server_name, stream)?;
 
         Ok(Conn { stream })
     }
 
+    ///Adds the PEM-encoded certificates from `file_path` to the trusted root store.
     #[cfg(feature = "rust-tls")]
     pub fn add_root_cert_file_pem(&mut self, file_path: &Path) -> Result<&mut Self, HttpError> {
-        let f = File::open(file_path)?;
-        let mut f = BufReader::new(f);
+        self.add_root_cert_pem(&read_file(file_path)?)
+    }
+
+    ///Adds PEM-encoded certificates to the trusted root store.
+    #[cfg(feature = "rust-tls")]
+    pub fn add_root_cert_pem(&mut self, pem: &[u8]) -> Result<&mut Self, HttpError> {
         let config = std::sync::Arc::make_mut(&mut self.client_config);
-        let _ = config
+        let (added, _) = config
             .root_store
-            .add_pem_file(&mut f)
+            .add_pem_file(&mut &pem[..])
             .map_err(|_| HttpError::from(ParseErr::Invalid))?;
-        Ok(self)
+
+        if added == 0 {
+            return Err(HttpError::from(ParseErr::Empty));
+        }
+        Ok(self.trust_changed())
+    }
+
+    ///Adds a DER-encoded certificate to the trusted root store.
+    #[cfg(feature = "rust-tls")]
+    pub fn add_root_cert_der(&mut self, der: &[u8]) -> Result<&mut Self, HttpError> {
+        let config = std::sync::Arc::make_mut(&mut self.client_config);
+        config
+            .root_store
+            .add(&rustls::Certificate(der.to_vec()))
+            .map_err(|_| HttpError::from(ParseErr::Invalid))?;
+        Ok(self.trust_changed())
+    }
+
+    ///Removes all certificates from the trusted root store, including the default
+    ///`webpki_roots` ones.
+    ///
+    ///Only certificates added afterwards will be trusted.
+    #[cfg(feature = "rust-tls")]
+    pub fn clear_root_certs(&mut self) -> &mut Self {
+        let config = std::sync::Arc::make_mut(&mut self.client_config);
+        config.root_store = rustls::RootCertStore::empty();
+        self.trust_changed()
+    }
+
+    ///Sets the client certificate chain and its private key, both PEM-encoded,
+    ///presented to servers that request client authentication.
+    ///
+    ///The key may be either in PKCS#8 or in RSA (PKCS#1) format.
+    #[cfg(feature = "rust-tls")]
+    pub fn set_client_cert_pem(
+        &mut self,
+        cert_chain: &[u8],
+        key: &[u8],
+    ) -> Result<&mut Self, HttpError> {
+        use rustls::internal::pemfile;
+
+        let certs =
+            pemfile::certs(&mut &cert_chain[..]).map_err(|_| HttpError::from(ParseErr::Invalid))?;
+        let mut keys = pemfile::pkcs8_private_keys(&mut &key[..])
+            .map_err(|_| HttpError::from(ParseErr::Invalid))?;
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut &key[..])
+                .map_err(|_| HttpError::from(ParseErr::Invalid))?;
+        }
+
+        match keys.into_iter().next() {
+            Some(key) if !certs.is_empty() => self.set_client_cert(certs, key),
+            _ => Err(HttpError::from(ParseErr::Empty)),
+        }
+    }
+
+    ///Sets the client certificate chain and its private key, both DER-encoded,
+    ///presented to servers that request client authentication.
+    ///
+    ///The chain starts with the end-entity certificate. The key may be either
+    ///in PKCS#8 or in RSA (PKCS#1) format.
+    #[cfg(feature = "rust-tls")]
+    pub fn set_client_cert_der(
+        &mut self,
+        cert_chain: &[&[u8]],
+        key: &[u8],
+    ) -> Result<&mut Self, HttpError> {
+        if cert_chain.is_empty() {
+            return Err(HttpError::from(ParseErr::Empty));
+        }
+
+        let certs = cert_chain
+            .iter()
+            .map(|der| rustls::Certificate(der.to_vec()))
+            .collect();
+        self.set_client_cert(certs, rustls::PrivateKey(key.to_vec()))
+    }
+
+    ///Reads the client certificate chain and its private key from PEM files.
+    #[cfg(feature = "rust-tls")]
+    pub fn set_client_cert_file_pem(
+        &mut self,
+        cert_path: &Path,
+        key_path: &Path,
+    ) -> Result<&mut Self, HttpError> {
+        let cert_chain = read_file(cert_path)?;
+        let key = read_file(key_path)?;
+        self.set_client_cert_pem(&cert_chain, &key)
+    }
+
+    #[cfg(feature = "rust-tls")]
+    fn set_client_cert(
+        &mut self,
+        certs: Vec<rustls::Certificate>,
+        key: rustls::PrivateKey,
+    ) -> Result<&mut Self, HttpError> {
+        let config = std::sync::Arc::make_mut(&mut self.client_config);
+        config
+            .set_single_client_cert(certs, key)
+            .map_err(|_| HttpError::Tls)?;
+        Ok(self.trust_changed())
+    }
+
+    ///Pins the server's end-entity certificate. Once at least one pin is added, connections
+    ///are established only if the certificate matches any of the pins.
+    ///
+    ///By default, pins are checked in addition to regular validation of the certificate chain.
+    #[cfg(feature = "rust-tls")]
+    pub fn add_pin(&mut self, pin: Pin) -> &mut Self {
+        self.pins.push(pin);
+        self.trust_changed()
+    }
+
+    ///Sets whether a certificate matching a pin is accepted without validating its chain
+    ///against the trusted root store. Has no effect when there are no pins.
+    #[cfg(feature = "rust-tls")]
+    pub fn set_pins_only(&mut self, pins_only: bool) -> &mut Self {
+        self.pins_only = pins_only;
+        self.trust_changed()
+    }
+
+    ///Accepts only RA-TLS certificates of SGX enclaves, checked by `verifier`, instead of
+    ///certificates issued by the trusted root store. Pins are still checked, if added.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::{ratls::{Policy, QuoteVerifier, Verifier}, tls::Config};
+    ///use rustls::TLSError;
+    ///
+    ///struct Dcap;
+    ///
+    ///impl QuoteVerifier for Dcap {
+    ///    fn verify_quote(&self, quote: &[u8]) -> Result<(), TLSError> {
+    ///        //verify quote signature and collateral here
+    ///        Ok(())
+    ///    }
+    ///}
+    ///
+    ///let mut policy = Policy::new();
+    ///policy.mr_signer([0x22; 32]).isv_prod_id(1);
+    ///
+    ///let mut cnf = Config::default();
+    ///cnf.set_ra_tls_verifier(Verifier::new(policy, Dcap));
+    ///```
+    #[cfg(feature = "rust-tls")]
+    pub fn set_ra_tls_verifier(&mut self, verifier: crate::ratls::Verifier) -> &mut Self {
+        self.with_verifier(Arc::new(verifier))
+    }
+
+    ///Verifies the server's certificate chain with `verifier` instead of validating it
+    ///against the trusted root store. Pins are still checked, if added.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::tls::Config;
+    ///use rustls::{
+    ///    Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError,
+    ///};
+    ///use std::sync::Arc;
+    ///
+    ///struct Internal;
+    ///
+    ///impl ServerCertVerifier for Internal {
+    ///    fn verify_server_cert(
+    ///        &self,
+    ///        _roots: &RootCertStore,
+    ///        presented_certs: &[Certificate],
+    ///        _dns_name: webpki::DNSNameRef,
+    ///        _ocsp_response: &[u8],
+    ///    ) -> Result<ServerCertVerified, TLSError> {
+    ///        //check presented certificates here
+    ///        Err(TLSError::NoCertificatesPresented)
+    ///    }
+    ///}
+    ///
+    ///let mut cnf = Config::default();
+    ///cnf.with_verifier(Arc::new(Internal));
+    ///```
+    #[cfg(feature = "rust-tls")]
+    pub fn with_verifier(&mut self, verifier: Arc<dyn ServerCertVerifier>) -> &mut Self {
+        self.verifier = Some(verifier);
+        self.trust_changed()
+    }
+
+    ///Sets whether the server's certificate is accepted without any verification.
+    ///Pins are still checked, if added.
+    ///
+    ///**Warning**: any certificate, including expired and self-signed ones, is then trusted,
+    ///which makes the connection open to man-in-the-middle attacks. Use only for testing.
+    #[cfg(feature = "rust-tls")]
+    pub fn danger_accept_invalid_certs(&mut self, accept: bool) -> &mut Self {
+        self.accept_invalid_certs = accept;
+        self.trust_changed()
+    }
+
+    ///Sets whether the server's certificate is accepted even if it is not valid
+    ///for the hostname the connection is made to.
+    ///
+    ///**Warning**: any certificate issued by a trusted root is then accepted for any server,
+    ///which makes the connection open to man-in-the-middle attacks. Use only for testing.
+    #[cfg(feature = "rust-tls")]
+    pub fn danger_accept_invalid_hostnames(&mut self, accept: bool) -> &mut Self {
+        self.accept_invalid_hostnames = accept;
+        self.trust_changed()
+    }
+
+    ///Sets the application protocols offered to the server with ALPN, in order of preference.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::tls::Config;
+    ///
+    ///let mut cnf = Config::default();
+    ///cnf.set_alpn_protocols(&["http/1.1"]);
+    ///```
+    #[cfg(feature = "rust-tls")]
+    pub fn set_alpn_protocols(&mut self, protocols: &[&str]) -> &mut Self {
+        let protocols: Vec<_> = protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+        Arc::make_mut(&mut self.client_config).set_protocols(&protocols);
+        self.prepare()
+    }
+
+    ///Sets whether the server name is sent with the SNI extension. Enabled by default.
+    #[cfg(feature = "rust-tls")]
+    pub fn set_sni(&mut self, enabled: bool) -> &mut Self {
+        Arc::make_mut(&mut self.client_config).enable_sni = enabled;
+        self.prepare()
+    }
+
+    ///Restricts the connection to given versions of the TLS protocol.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::tls::{Config, Version};
+    ///
+    ///let mut cnf = Config::default();
+    ///cnf.set_versions(&[Version::Tls13]).unwrap();
+    ///```
+    #[cfg(feature = "rust-tls")]
+    pub fn set_versions(&mut self, versions: &[Version]) -> Result<&mut Self, HttpError> {
+        if versions.is_empty() {
+            return Err(HttpError::Tls);
+        }
+
+        Arc::make_mut(&mut self.client_config).versions = versions
+            .iter()
+            .map(|version| match version {
+                Version::Tls12 => rustls::ProtocolVersion::TLSv1_2,
+                Version::Tls13 => rustls::ProtocolVersion::TLSv1_3,
+            })
+            .collect();
+        Ok(self.prepare())
+    }
+
+    ///Restricts the connection to given cipher suites, in order of preference.
+    ///Fails if any of the cipher suites is not supported.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::tls::Config;
+    ///use rustls::CipherSuite;
+    ///
+    ///let mut cnf = Config::default();
+    ///cnf.set_cipher_suites(&[
+    ///    CipherSuite::TLS13_AES_256_GCM_SHA384,
+    ///    CipherSuite::TLS13_AES_128_GCM_SHA256,
+    ///])
+    ///.unwrap();
+    ///```
+    #[cfg(feature = "rust-tls")]
+    pub fn set_cipher_suites(
+        &mut self,
+        suites: &[rustls::CipherSuite],
+    ) -> Result<&mut Self, HttpError> {
+        let ciphersuites = suites
+            .iter()
+            .map(|suite| {
+                rustls::ALL_CIPHERSUITES
+                    .iter()
+                    .find(|supported| supported.suite == *suite)
+                    .copied()
+                    .ok_or(HttpError::Tls)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if ciphersuites.is_empty() {
+            return Err(HttpError::Tls);
+        }
+
+        Arc::make_mut(&mut self.client_config).ciphersuites = ciphersuites;
+        Ok(self.prepare())
+    }
+
+    ///Sets the maximum number of TLS sessions stored for resumption. Sessions are
+    ///not stored if `capacity` is 0. The session cache is shared by clones of this `Config`.
+    #[cfg(feature = "rust-tls")]
+    pub fn set_session_cache(&mut self, capacity: usize) -> &mut Self {
+        let cache: Arc<dyn rustls::StoresClientSessions> = match capacity {
+            0 => Arc::new(rustls::NoClientSessionStorage {}),
+            _ => rustls::ClientSessionMemoryCache::new(capacity),
+        };
+
+        Arc::make_mut(&mut self.client_config).set_persistence(cache);
+        self.session_capacity = capacity;
+        self.prepare()
+    }
+
+    ///Gives this `Config` a new, empty session cache after the verification of the server
+    ///changed, so that sessions verified with the previous settings aren't resumed.
+    #[cfg(feature = "rust-tls")]
+    fn trust_changed(&mut self) -> &mut Self {
+        self.set_session_cache(self.session_capacity)
+    }
+
+    ///Builds the connector from the current settings, once they change, rather than
+    ///on every connection.
+    #[cfg(feature = "rust-tls")]
+    fn prepare(&mut self) -> &mut Self {
+        let mut config = (*self.client_config).clone();
+        let roots = std::mem::replace(&mut config.root_store, RootCertStore::empty());
+
+        self.connector = Arc::new(Connector {
+            config,
+            roots: Arc::new(roots),
+            verifier: self.verifier(),
+        });
+        self
+    }
+
+    ///Returns verifier of the server's certificate chain, composed from the current settings.
+    #[cfg(feature = "rust-tls")]
+    fn verifier(&self) -> Arc<dyn ServerCertVerifier> {
+        let mut verifier: Arc<dyn ServerCertVerifier> = match &self.verifier {
+            _ if self.accept_invalid_certs => Arc::new(NoVerifier),
+            Some(verifier) => verifier.clone(),
+            None => Arc::new(rustls::WebPKIVerifier::new()),
+        };
+
+        if self.accept_invalid_hostnames && !self.accept_invalid_certs {
+            verifier = Arc::new(AnyHostnameVerifier { inner: verifier });
+        }
+
+        if !self.pins.is_empty() {
+            verifier = Arc::new(PinnedVerifier {
+                pins: self.pins.clone(),
+                inner: if self.pins_only { None } else { Some(verifier) },
+            });
+        }
+
+        verifier
     }
 
     #[cfg(feature = "rust-tls")]
@@ -144,13 +792,1016 @@ impl Config {
     {
         use rustls::{ClientSession, StreamOwned};
 
+        //Only the verifier is set per connection, to learn whether the session was resumed.
+        //The root store, which is the costly part to copy, is shared.
+        let verified = Arc::new(AtomicBool::new(false));
+        let mut config = self.connector.config.clone();
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(ObservedVerifier {
+                inner: self.connector.verifier.clone(),
+                roots: self.connector.roots.clone(),
+                verified: verified.clone(),
+            }));
+
+        let server_name = self.server_name.as_deref().unwrap_or(hostname.as_ref());
         let session = ClientSession::new(
-            &self.client_config,
-            webpki::DNSNameRef::try_from_ascii_str(hostname.as_ref())
-                .map_err(|_| HttpError::Tls)?,
+            &Arc::new(config),
+            webpki::DNSNameRef::try_from_ascii_str(server_name).map_err(|_| HttpError::Tls)?,
         );
         let stream = StreamOwned::new(session, stream);
 
-        Ok(Conn { stream })
+        Ok(Conn {
+            stream,
+            verified,
+            close_notify: false,
+        })
+    }
+}
+
+///Version of the TLS protocol
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Version {
+    ///TLS 1.2
+    Tls12,
+    ///TLS 1.3
+    Tls13,
+}
+
+#[cfg(feature = "rust-tls")]
+impl Version {
+    fn from_rustls(version: rustls::ProtocolVersion) -> Option<Version> {
+        match version {
+            rustls::ProtocolVersion::TLSv1_2 => Some(Version::Tls12),
+            rustls::ProtocolVersion::TLSv1_3 => Some(Version::Tls13),
+            _ => None,
+        }
+    }
+}
+
+///Details of an established TLS connection
+#[derive(Debug, PartialEq, Clone)]
+pub struct TlsInfo {
+    version: Option<Version>,
+    cipher_suite: Option<String>,
+    alpn_protocol: Option<Vec<u8>>,
+    peer_certificates: Vec<Vec<u8>>,
+    resumed: bool,
+}
+
+impl TlsInfo {
+    ///Returns the negotiated version of the TLS protocol, if known.
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    ///Returns name of the negotiated cipher suite, e.g. `TLS13_AES_256_GCM_SHA384`, if known.
+    pub fn cipher_suite(&self) -> Option<&str> {
+        self.cipher_suite.as_deref()
+    }
+
+    ///Returns the application protocol negotiated with ALPN, if any.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+
+    ///Returns DER-encoded certificate chain presented by the server,
+    ///starting with its end-entity certificate.
+    pub fn peer_certificates(&self) -> &[Vec<u8>] {
+        &self.peer_certificates
+    }
+
+    ///Checks if the TLS session was resumed.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+}
+
+///Pin of the server's end-entity certificate
+#[cfg(feature = "rust-tls")]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Pin {
+    ///SHA-256 hash of the DER-encoded `SubjectPublicKeyInfo` of the certificate
+    SpkiSha256([u8; 32]),
+    ///SHA-256 fingerprint of the DER-encoded certificate
+    CertSha256([u8; 32]),
+}
+
+#[cfg(feature = "rust-tls")]
+impl Pin {
+    ///Creates `Pin::SpkiSha256` matching the public key of a DER-encoded certificate.
+    pub fn spki_of(cert: &[u8]) -> Result<Pin, HttpError> {
+        let spki = crate::der::subject_public_key_info(cert)?;
+        Ok(Pin::SpkiSha256(sha256(spki)))
+    }
+
+    ///Creates `Pin::CertSha256` matching a DER-encoded certificate.
+    pub fn cert_of(cert: &[u8]) -> Pin {
+        Pin::CertSha256(sha256(cert))
+    }
+
+    ///Checks if a DER-encoded certificate matches this `Pin`.
+    pub fn matches(&self, cert: &[u8]) -> bool {
+        match self {
+            Pin::SpkiSha256(hash) => crate::der::subject_public_key_info(cert)
+                .map(|spki| sha256(spki) == *hash)
+                .unwrap_or(false),
+            Pin::CertSha256(hash) => sha256(cert) == *hash,
+        }
+    }
+}
+
+///Checks the server's end-entity certificate against a set of pins,
+///after the optional `inner` verifier accepts the certificate chain.
+#[cfg(feature = "rust-tls")]
+struct PinnedVerifier {
+    pins: Vec<Pin>,
+    inner: Option<Arc<dyn ServerCertVerifier>>,
+}
+
+#[cfg(feature = "rust-tls")]
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        dns_name: webpki::DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        if let Some(inner) = &self.inner {
+            inner.verify_server_cert(roots, presented_certs, dns_name, ocsp_response)?;
+        }
+
+        let cert = presented_certs
+            .first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+
+        if self.pins.iter().any(|pin| pin.matches(&cert.0)) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TLSError::General(format!(
+                "certificate pin mismatch: certificate sha256 {}, public key sha256 {}",
+                to_hex(&sha256(&cert.0)),
+                crate::der::subject_public_key_info(&cert.0)
+                    .map(|spki| to_hex(&sha256(spki)))
+                    .unwrap_or_else(|_| "unknown".to_string()),
+            )))
+        }
+    }
+}
+
+///Session configuration, root store and verifier of a `Config`, built once its settings change.
+///The root store is kept apart from the session configuration, which is copied for
+///every connection.
+#[cfg(feature = "rust-tls")]
+struct Connector {
+    config: rustls::ClientConfig,
+    roots: Arc<RootCertStore>,
+    verifier: Arc<dyn ServerCertVerifier>,
+}
+
+#[cfg(feature = "rust-tls")]
+impl Default for Connector {
+    fn default() -> Self {
+        Connector {
+            config: rustls::ClientConfig::new(),
+            roots: Arc::new(RootCertStore::empty()),
+            verifier: Arc::new(rustls::WebPKIVerifier::new()),
+        }
+    }
+}
+
+///Records that the `inner` verifier was asked to verify a certificate chain,
+///which happens only during full handshakes. The chain is verified against `roots`.
+#[cfg(feature = "rust-tls")]
+struct ObservedVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    roots: Arc<RootCertStore>,
+    verified: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "rust-tls")]
+impl ServerCertVerifier for ObservedVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        dns_name: webpki::DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        self.verified.store(true, Ordering::SeqCst);
+        self.inner
+            .verify_server_cert(&self.roots, presented_certs, dns_name, ocsp_response)
+    }
+}
+
+///Accepts any certificate chain.
+#[cfg(feature = "rust-tls")]
+struct NoVerifier;
+
+#[cfg(feature = "rust-tls")]
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        _presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+///Accepts certificate chains accepted by the `inner` verifier, ignoring
+///whether the certificate is valid for the server's hostname.
+#[cfg(feature = "rust-tls")]
+struct AnyHostnameVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+}
+
+#[cfg(feature = "rust-tls")]
+impl ServerCertVerifier for AnyHostnameVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        dns_name: webpki::DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        match self
+            .inner
+            .verify_server_cert(roots, presented_certs, dns_name, ocsp_response)
+        {
+            Err(TLSError::WebPKIError(webpki::Error::CertNotValidForName)) => {
+                Ok(ServerCertVerified::assertion())
+            }
+            res => res,
+        }
+    }
+}
+
+#[cfg(feature = "rust-tls")]
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, data).as_ref());
+    hash
+}
+
+#[cfg(feature = "rust-tls")]
+pub(crate) fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_file(file_path: &Path) -> Result<Vec<u8>, HttpError> {
+    let mut content = Vec::new();
+    File::open(file_path)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "rust-tls")]
+    use std::thread::JoinHandle;
+    use std::{net::TcpStream, thread};
+
+    const CLIENT_CERT: &[u8] = include_bytes!("../tests/certs/client.pem");
+    const CLIENT_KEY: &[u8] = include_bytes!("../tests/certs/client.key");
+    #[cfg(feature = "rust-tls")]
+    const CLIENT_KEY_RSA: &[u8] = include_bytes!("../tests/certs/client_rsa.key");
+    #[cfg(feature = "rust-tls")]
+    const CLIENT_CERT_DER: &[u8] = include_bytes!("../tests/certs/client.der");
+    #[cfg(feature = "rust-tls")]
+    const CLIENT_KEY_DER: &[u8] = include_bytes!("../tests/certs/client.key.der");
+
+    const CA_CERT: &[u8] = include_bytes!("../tests/certs/ca.pem");
+    const SERVER_CERT: &[u8] = include_bytes!("../tests/certs/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("../tests/certs/server.key");
+    #[cfg(feature = "rust-tls")]
+    const SERVER_SPKI_SHA256: &str =
+        "1185f488dfe189d74d1e2a7ee8da0a6b0f630d6ec9da6d0f0c419ff01a3ff717";
+    #[cfg(feature = "rust-tls")]
+    const SERVER_CERT_SHA256: &str =
+        "37ff61b36e5820fa8c60958b1772682d21171ba0f1d9fd23f32accea44b57256";
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+    const CA_CERT_DER: &[u8] = include_bytes!("../tests/certs/ca.der");
+    #[cfg(feature = "rust-tls")]
+    const RA_TLS_CERT: &[u8] = include_bytes!("../tests/certs/ratls.pem");
+    #[cfg(feature = "rust-tls")]
+    const RA_TLS_KEY: &[u8] = include_bytes!("../tests/certs/ratls.key");
+    #[cfg(feature = "rust-tls")]
+    const RA_TLS_QUOTE: &[u8] = include_bytes!("../tests/certs/ratls_quote.bin");
+
+    #[cfg(feature = "rust-tls")]
+    fn root_certs_len(cnf: &Config) -> usize {
+        cnf.client_config.root_store.len()
+    }
+
+    #[cfg(feature = "rust-tls")]
+    fn server_cert_der() -> Vec<u8> {
+        rustls::internal::pemfile::certs(&mut &SERVER_CERT[..]).unwrap()[0]
+            .0
+            .clone()
+    }
+
+    #[cfg(feature = "rust-tls")]
+    fn server_config(cert: &[u8], key: &[u8]) -> rustls::ServerConfig {
+        use rustls::internal::pemfile;
+
+        let certs = pemfile::certs(&mut &cert[..]).unwrap();
+        let key = pemfile::pkcs8_private_keys(&mut &key[..]).unwrap()[0].clone();
+        let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+        config.set_single_cert(certs, key).unwrap();
+        config
+    }
+
+    ///What the test server observed.
+    #[cfg(feature = "rust-tls")]
+    #[derive(Debug, PartialEq)]
+    struct Served {
+        sni: Option<String>,
+        close_notify: bool,
+    }
+
+    ///Starts a TLS server on localhost, which answers a single connection with `RESPONSE`,
+    ///ending with `close_notify`, and then waits until the client closes the connection.
+    #[cfg(feature = "rust-tls")]
+    fn serve(config: rustls::ServerConfig) -> (u16, JoinHandle<Served>) {
+        serve_with(config, RESPONSE, true)
+    }
+
+    ///Starts a TLS server on localhost, which answers a single connection with `response`.
+    #[cfg(feature = "rust-tls")]
+    fn serve_with(
+        config: rustls::ServerConfig,
+        response: &'static [u8],
+        close_notify: bool,
+    ) -> (u16, JoinHandle<Served>) {
+        use rustls::Session;
+        use std::net::TcpListener;
+
+        let config = Arc::new(config);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let session = rustls::ServerSession::new(&config);
+            let mut stream = rustls::StreamOwned::new(session, stream);
+            let mut buf = [0; 1024];
+
+            if stream.read(&mut buf).is_ok() {
+                let _ = stream.write_all(response);
+                if close_notify {
+                    stream.sess.send_close_notify();
+                }
+                let _ = stream.flush();
+            }
+
+            let sni = stream.sess.get_sni_hostname().map(String::from);
+            let close_notify = close_notify
+                && match stream.read(&mut buf) {
+                    Err(e) => e.kind() == io::ErrorKind::ConnectionAborted,
+                    Ok(_) => false,
+                };
+
+            Served { sni, close_notify }
+        });
+
+        (port, handle)
+    }
+
+    #[cfg(feature = "rust-tls")]
+    fn serve_tls(cert: &[u8], key: &[u8]) -> u16 {
+        serve(server_config(cert, key)).0
+    }
+
+    ///Starts a TLS server on localhost, which answers a single connection with `RESPONSE`.
+    #[cfg(feature = "native-tls")]
+    fn serve_tls(cert: &[u8], key: &[u8]) -> u16 {
+        use std::net::TcpListener;
+
+        let identity = native_tls::Identity::from_pkcs8(cert, key).unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            if let Ok(mut stream) = acceptor.accept(stream) {
+                let mut buf = [0; 1024];
+
+                if stream.read(&mut buf).is_ok() {
+                    let _ = stream.write_all(RESPONSE);
+                    let _ = stream.shutdown();
+                }
+            }
+        });
+
+        port
+    }
+
+    ///Connects to `hostname` served by a local server listening on `port` using `cnf`.
+    fn connect(cnf: &Config, hostname: &str, port: u16) -> Result<Conn<TcpStream>, HttpError> {
+        let stream = TcpStream::connect(("127.0.0.1", port))?;
+        cnf.connect(hostname, stream)
+    }
+
+    ///Sends a request over `conn`. Returns the response.
+    fn request<S: io::Read + io::Write>(conn: &mut Conn<S>) -> io::Result<Vec<u8>> {
+        let mut res = Vec::new();
+
+        conn.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+        conn.read_to_end(&mut res)?;
+        Ok(res)
+    }
+
+    ///Sends a request to a local TLS server using `cnf`. Returns the response.
+    fn exchange(cnf: &Config) -> io::Result<Vec<u8>> {
+        exchange_with(cnf, "localhost", SERVER_CERT, SERVER_KEY)
+    }
+
+    ///Sends a request to `hostname` served by a local TLS server presenting `cert` using `cnf`.
+    ///Returns the response.
+    fn exchange_with(cnf: &Config, hostname: &str, cert: &[u8], key: &[u8]) -> io::Result<Vec<u8>> {
+        let port = serve_tls(cert, key);
+        let mut conn = connect(cnf, hostname, port)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        request(&mut conn)
+    }
+
+    #[cfg(feature = "rust-tls")]
+    fn pin_mismatch(res: io::Result<Vec<u8>>) -> bool {
+        match res {
+            Err(e) => e.to_string().contains("certificate pin mismatch"),
+            Ok(_) => false,
+        }
+    }
+
+    #[cfg(feature = "rust-tls")]
+    fn has_client_cert(cnf: &Config) -> bool {
+        cnf.client_config.client_auth_cert_resolver.has_certs()
+    }
+
+    ///Sends a request using `cnf` to a local TLS server, which requires a client certificate.
+    ///Returns the response.
+    #[cfg(feature = "rust-tls")]
+    fn client_auth(cnf: &Config) -> io::Result<Vec<u8>> {
+        let (port, _) = serve(crate::test_server::client_auth_config());
+        let mut conn = connect(cnf, "localhost", port)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        request(&mut conn)
+    }
+
+    #[test]
+    fn config_add_root_cert_pem() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        assert!(cnf.add_root_cert_pem(b"").is_err());
+        assert!(cnf.add_root_cert_pem(CLIENT_KEY).is_err());
+    }
+
+    #[test]
+    fn config_add_root_cert_der() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_der(CA_CERT_DER).unwrap();
+
+        assert!(cnf.add_root_cert_der(b"not a certificate").is_err());
+    }
+
+    #[test]
+    fn config_add_root_cert_file_pem() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_file_pem(Path::new("tests/certs/ca.pem"))
+            .unwrap();
+
+        assert!(cnf
+            .add_root_cert_file_pem(Path::new("tests/certs/missing.pem"))
+            .is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_clear_root_certs() {
+        let mut cnf = Config::default();
+        assert!(root_certs_len(&cnf) > 1);
+
+        cnf.clear_root_certs();
+        assert_eq!(root_certs_len(&cnf), 0);
+
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+        cnf.add_root_cert_der(CA_CERT_DER).unwrap();
+        assert_eq!(root_certs_len(&cnf), 2);
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn pin_of_cert() {
+        let cert = server_cert_der();
+        let spki_pin = Pin::spki_of(&cert).unwrap();
+        let cert_pin = Pin::cert_of(&cert);
+
+        match spki_pin {
+            Pin::SpkiSha256(hash) => assert_eq!(to_hex(&hash), SERVER_SPKI_SHA256),
+            other => panic!("Expected SpkiSha256, got: {:?}", other),
+        }
+        assert_eq!(cert_pin, Pin::CertSha256(sha256(&cert)));
+        assert_eq!(to_hex(&sha256(&cert)), SERVER_CERT_SHA256);
+
+        assert!(spki_pin.matches(&cert));
+        assert!(cert_pin.matches(&cert));
+        assert!(!spki_pin.matches(CA_CERT_DER));
+        assert!(!cert_pin.matches(CA_CERT_DER));
+        assert!(Pin::spki_of(b"not a certificate").is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_pin_match() {
+        let cert = server_cert_der();
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT)
+            .unwrap()
+            .add_pin(Pin::cert_of(CA_CERT_DER))
+            .add_pin(Pin::spki_of(&cert).unwrap());
+
+        assert_eq!(exchange(&cnf).unwrap(), RESPONSE);
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_pin_mismatch() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT)
+            .unwrap()
+            .add_pin(Pin::spki_of(CA_CERT_DER).unwrap());
+
+        assert!(pin_mismatch(exchange(&cnf)));
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_pins_only() {
+        let cert = server_cert_der();
+        let mut cnf = Config::default();
+        cnf.add_pin(Pin::cert_of(&cert));
+
+        assert!(exchange(&cnf).is_err());
+
+        cnf.set_pins_only(true);
+        assert_eq!(exchange(&cnf).unwrap(), RESPONSE);
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_ra_tls_verifier() {
+        use crate::ratls::{Policy, QuoteVerifier, Verifier};
+
+        struct RecordedQuote;
+
+        impl QuoteVerifier for RecordedQuote {
+            fn verify_quote(&self, quote: &[u8]) -> Result<(), TLSError> {
+                if quote == RA_TLS_QUOTE {
+                    Ok(())
+                } else {
+                    Err(TLSError::General("unknown quote".to_string()))
+                }
+            }
+        }
+
+        let mut policy = Policy::new();
+        policy.mr_enclave([0x11; 32]);
+
+        let mut cnf = Config::default();
+        assert!(exchange_with(&cnf, "localhost", RA_TLS_CERT, RA_TLS_KEY).is_err());
+
+        cnf.set_ra_tls_verifier(Verifier::new(policy.clone(), RecordedQuote));
+        assert_eq!(
+            exchange_with(&cnf, "localhost", RA_TLS_CERT, RA_TLS_KEY).unwrap(),
+            RESPONSE
+        );
+        assert!(exchange(&cnf).is_err());
+
+        policy.mr_enclave([0; 32]);
+        cnf.set_ra_tls_verifier(Verifier::new(policy, RecordedQuote));
+        assert!(exchange_with(&cnf, "localhost", RA_TLS_CERT, RA_TLS_KEY).is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_with_verifier() {
+        struct Expect(Vec<u8>);
+
+        impl ServerCertVerifier for Expect {
+            fn verify_server_cert(
+                &self,
+                _roots: &RootCertStore,
+                presented_certs: &[rustls::Certificate],
+                _dns_name: webpki::DNSNameRef,
+                _ocsp_response: &[u8],
+            ) -> Result<ServerCertVerified, TLSError> {
+                if presented_certs[0].0 == self.0 {
+                    Ok(ServerCertVerified::assertion())
+                } else {
+                    Err(TLSError::General("unexpected certificate".to_string()))
+                }
+            }
+        }
+
+        let mut cnf = Config::default();
+        cnf.with_verifier(Arc::new(Expect(server_cert_der())));
+        assert_eq!(exchange(&cnf).unwrap(), RESPONSE);
+
+        cnf.with_verifier(Arc::new(Expect(CA_CERT_DER.to_vec())));
+        match exchange(&cnf) {
+            Err(e) => assert!(e.to_string().contains("unexpected certificate")),
+            Ok(_) => panic!("Expected rejected certificate"),
+        }
+    }
+
+    #[test]
+    fn config_danger_accept_invalid_certs() {
+        let mut cnf = Config::default();
+        assert!(exchange(&cnf).is_err());
+
+        cnf.danger_accept_invalid_certs(true);
+        assert_eq!(exchange(&cnf).unwrap(), RESPONSE);
+        assert_eq!(
+            exchange_with(&cnf, "example.com", SERVER_CERT, SERVER_KEY).unwrap(),
+            RESPONSE
+        );
+
+        #[cfg(feature = "rust-tls")]
+        {
+            cnf.add_pin(Pin::cert_of(CA_CERT_DER));
+            assert!(pin_mismatch(exchange(&cnf)));
+        }
+    }
+
+    #[test]
+    fn config_danger_accept_invalid_hostnames() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+        assert!(exchange_with(&cnf, "example.com", SERVER_CERT, SERVER_KEY).is_err());
+
+        cnf.danger_accept_invalid_hostnames(true);
+        assert_eq!(
+            exchange_with(&cnf, "example.com", SERVER_CERT, SERVER_KEY).unwrap(),
+            RESPONSE
+        );
+
+        cnf.clear_root_certs();
+        assert!(exchange_with(&cnf, "example.com", SERVER_CERT, SERVER_KEY).is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_alpn_protocols() {
+        let mut server = server_config(SERVER_CERT, SERVER_KEY);
+        server.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        let (port, _) = serve(server.clone());
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert_eq!(conn.alpn_protocol(), None);
+
+        cnf.set_alpn_protocols(&["spdy/3", "http/1.1"]);
+        let (port, _) = serve(server);
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert_eq!(conn.alpn_protocol(), Some(&b"http/1.1"[..]));
+    }
+
+    #[test]
+    fn config_connect() {
+        let mut cnf = Config::default();
+        assert!(exchange(&cnf).is_err());
+
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+        assert_eq!(exchange(&cnf).unwrap(), RESPONSE);
+        assert!(exchange_with(&cnf, "127.0.0.1", SERVER_CERT, SERVER_KEY).is_err());
+
+        cnf.set_server_name("localhost");
+        assert_eq!(
+            exchange_with(&cnf, "127.0.0.1", SERVER_CERT, SERVER_KEY).unwrap(),
+            RESPONSE
+        );
+
+        cnf.clear_root_certs();
+        assert!(exchange(&cnf).is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_server_name() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        let (port, _) = serve(server_config(SERVER_CERT, SERVER_KEY));
+        assert!(connect(&cnf, "127.0.0.1", port).is_err());
+
+        cnf.set_server_name("localhost");
+        let (port, handle) = serve(server_config(SERVER_CERT, SERVER_KEY));
+        let mut conn = connect(&cnf, "127.0.0.1", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+
+        drop(conn);
+        assert_eq!(handle.join().unwrap().sni, Some("localhost".to_string()));
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_sni_disabled() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap().set_sni(false);
+
+        let (port, handle) = serve(server_config(SERVER_CERT, SERVER_KEY));
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+
+        drop(conn);
+        assert_eq!(handle.join().unwrap().sni, None);
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_versions() {
+        let mut server = server_config(SERVER_CERT, SERVER_KEY);
+        server.versions = vec![rustls::ProtocolVersion::TLSv1_2];
+
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+        assert!(cnf.set_versions(&[]).is_err());
+
+        let (port, _) = serve(server.clone());
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+
+        cnf.set_versions(&[Version::Tls13]).unwrap();
+        let (port, _) = serve(server);
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert!(request(&mut conn).is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_cipher_suites() {
+        use rustls::{CipherSuite, Session};
+
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        assert!(cnf.set_cipher_suites(&[]).is_err());
+        assert!(cnf
+            .set_cipher_suites(&[CipherSuite::TLS_RSA_WITH_AES_128_CBC_SHA])
+            .is_err());
+
+        cnf.set_cipher_suites(&[CipherSuite::TLS13_CHACHA20_POLY1305_SHA256])
+            .unwrap();
+        let (port, _) = serve(server_config(SERVER_CERT, SERVER_KEY));
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert_eq!(
+            conn.stream.sess.get_negotiated_ciphersuite().unwrap().suite,
+            CipherSuite::TLS13_CHACHA20_POLY1305_SHA256
+        );
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn conn_read_close_notify() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        let (port, _) = serve(server_config(SERVER_CERT, SERVER_KEY));
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert!(!conn.is_closed());
+
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert!(conn.is_closed());
+        assert_eq!(conn.read(&mut [0; 16]).unwrap(), 0);
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn conn_read_without_close_notify() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        let config = server_config(SERVER_CERT, SERVER_KEY);
+        let (port, _) = serve_with(config, RESPONSE, false);
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert!(!conn.is_closed());
+        assert_eq!(conn.read(&mut [0; 16]).unwrap(), 0);
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn conn_shutdown() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        let (port, handle) = serve(server_config(SERVER_CERT, SERVER_KEY));
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        conn.shutdown().unwrap();
+        drop(conn);
+        assert!(handle.join().unwrap().close_notify);
+
+        let (port, handle) = serve(server_config(SERVER_CERT, SERVER_KEY));
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        drop(conn);
+        assert!(!handle.join().unwrap().close_notify);
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn conn_tls_info() {
+        let mut server = server_config(SERVER_CERT, SERVER_KEY);
+        server.set_protocols(&[b"http/1.1".to_vec()]);
+
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT)
+            .unwrap()
+            .set_alpn_protocols(&["http/1.1"])
+            .set_cipher_suites(&[rustls::CipherSuite::TLS13_AES_128_GCM_SHA256])
+            .unwrap();
+
+        let (port, _) = serve(server);
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(conn.protocol_version(), None);
+        assert!(conn.peer_certificates().is_empty());
+
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert_eq!(conn.protocol_version(), Some(Version::Tls13));
+        assert_eq!(
+            conn.cipher_suite(),
+            Some(rustls::CipherSuite::TLS13_AES_128_GCM_SHA256)
+        );
+        assert_eq!(conn.peer_certificates(), vec![server_cert_der()]);
+
+        let info = conn.tls_info();
+        assert_eq!(info.version(), Some(Version::Tls13));
+        assert_eq!(info.cipher_suite(), Some("TLS13_AES_128_GCM_SHA256"));
+        assert_eq!(info.alpn_protocol(), Some(&b"http/1.1"[..]));
+        assert_eq!(info.peer_certificates(), &[server_cert_der()]);
+        assert!(!info.is_resumed());
+    }
+
+    #[cfg(feature = "native-tls")]
+    #[test]
+    fn conn_tls_info() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        let port = serve_tls(SERVER_CERT, SERVER_KEY);
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+
+        let server_cert = native_tls::Certificate::from_pem(SERVER_CERT)
+            .unwrap()
+            .to_der()
+            .unwrap();
+        let info = conn.tls_info();
+        assert_eq!(info.peer_certificates(), &[server_cert]);
+        assert_eq!(info.version(), None);
+        assert!(!info.is_resumed());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_session_resumption() {
+        let server = server_config(SERVER_CERT, SERVER_KEY);
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+        let shared = cnf.clone();
+
+        let (port, _) = serve(server.clone());
+        let mut conn = connect(&cnf, "localhost", port).unwrap();
+        assert!(!conn.is_resumed());
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert!(!conn.is_resumed());
+
+        let (port, _) = serve(server.clone());
+        let mut conn = connect(&shared, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert!(conn.is_resumed());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_session_cache_trust_change() {
+        let server = server_config(SERVER_CERT, SERVER_KEY);
+        let mut strict = Config::default();
+        strict.add_root_cert_pem(CA_CERT).unwrap();
+        let mut insecure = strict.clone();
+        insecure.danger_accept_invalid_certs(true);
+
+        let (port, _) = serve(server.clone());
+        let mut conn = connect(&insecure, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+
+        let (port, _) = serve(server.clone());
+        let mut conn = connect(&strict, "localhost", port).unwrap();
+        assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+        assert!(!conn.is_resumed());
+
+        let mut pinned = strict.clone();
+        pinned.add_pin(Pin::CertSha256([0; 32]));
+
+        let (port, _) = serve(server);
+        let mut conn = connect(&pinned, "localhost", port).unwrap();
+        assert!(request(&mut conn).is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_session_cache_disabled() {
+        let server = server_config(SERVER_CERT, SERVER_KEY);
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap().set_session_cache(0);
+
+        for _ in 0..2 {
+            let (port, _) = serve(server.clone());
+            let mut conn = connect(&cnf, "localhost", port).unwrap();
+            assert_eq!(request(&mut conn).unwrap(), RESPONSE);
+            assert!(!conn.is_resumed());
+        }
+    }
+
+    #[test]
+    fn config_client_cert_pem() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_pem(CA_CERT).unwrap();
+
+        #[cfg(feature = "rust-tls")]
+        assert!(client_auth(&cnf).is_err());
+
+        cnf.set_client_cert_pem(CLIENT_CERT, CLIENT_KEY).unwrap();
+
+        #[cfg(feature = "rust-tls")]
+        {
+            assert!(has_client_cert(&cnf));
+            assert_eq!(client_auth(&cnf).unwrap(), RESPONSE);
+        }
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_client_cert_pem_rsa() {
+        let mut cnf = Config::default();
+        cnf.set_client_cert_pem(CLIENT_CERT, CLIENT_KEY_RSA)
+            .unwrap();
+
+        assert!(has_client_cert(&cnf));
+    }
+
+    #[test]
+    fn config_client_cert_pem_invalid() {
+        let mut cnf = Config::default();
+
+        assert!(cnf.set_client_cert_pem(CLIENT_CERT, b"").is_err());
+        assert!(cnf.set_client_cert_pem(b"", CLIENT_KEY).is_err());
+        assert!(cnf.set_client_cert_pem(CLIENT_KEY, CLIENT_CERT).is_err());
+    }
+
+    #[cfg(feature = "rust-tls")]
+    #[test]
+    fn config_client_cert_der() {
+        let mut cnf = Config::default();
+        cnf.add_root_cert_der(CA_CERT_DER).unwrap();
+        assert!(client_auth(&cnf).is_err());
+
+        cnf.set_client_cert_der(&[CLIENT_CERT_DER], CLIENT_KEY_DER)
+            .unwrap();
+
+        assert!(has_client_cert(&cnf));
+        assert_eq!(client_auth(&cnf).unwrap(), RESPONSE);
+        assert!(cnf.set_client_cert_der(&[], CLIENT_KEY_DER).is_err());
+        assert!(cnf.set_client_cert_der(&[CLIENT_CERT_DER], b"key").is_err());
+    }
+
+    #[test]
+    fn config_client_cert_file_pem() {
+        let mut cnf = Config::default();
+        cnf.set_client_cert_file_pem(
+            Path::new("tests/certs/client.pem"),
+            Path::new("tests/certs/client.key"),
+        )
+        .unwrap();
+
+        assert!(cnf
+            .set_client_cert_file_pem(
+                Path::new("tests/certs/missing.pem"),
+                Path::new("tests/certs/client.key"),
+            )
+            .is_err());
     }
 }
@@ -150,7 +150,7 @@ impl Uri {
     ///```
     pub fn corr_port(&self) -> u16 {
         let default_port = match self.scheme() {
-            "https" => HTTPS_PORT,
+            "https" | "wss" => HTTPS_PORT,
             _ => HTTP_PORT,
         };
 
@@ -220,6 +220,66 @@ impl Uri {
 
         result
     }
+
+    ///Resolves `reference` against this `Uri`, as described in RFC 3986, section 5.2.
+    ///`reference` may be either relative, e.g. `../baz`, or absolute.
+    ///
+    ///# Example
+    ///```
+    ///use http_req::uri::Uri;
+    ///
+    ///let uri: Uri = "https://foo.com/bar/baz?query".parse().unwrap();
+    ///assert_eq!(uri.join("qux").unwrap().to_string(), "https://foo.com/bar/qux");
+    ///assert_eq!(uri.join("/qux?a=b").unwrap().to_string(), "https://foo.com/qux?a=b");
+    ///assert_eq!(uri.join("//bar.com/").unwrap().to_string(), "https://bar.com/");
+    ///```
+    pub fn join(&self, reference: &str) -> Result<Uri, Error> {
+        let mut reference = reference.to_string();
+        remove_spaces(&mut reference);
+
+        if has_scheme(&reference) {
+            return reference.parse();
+        }
+
+        let base = &self.inner[..self.authority_end()];
+        let path = self.path().unwrap_or("");
+        let no_fragment = &self.inner[..self.fragment.map_or(self.inner.len(), |r| r.start - 1)];
+        let query_start = self
+            .query
+            .or(self.fragment)
+            .map_or(self.inner.len(), |r| r.start - 1);
+
+        let target = match reference.chars().next() {
+            None => no_fragment.to_string(),
+            Some('/') if reference.starts_with("//") => format!("{}:{}", self.scheme(), reference),
+            Some('/') => format!("{}{}", base, remove_dot_segments(&reference)),
+            Some('?') => format!("{}{}", &self.inner[..query_start], reference),
+            Some('#') => format!("{}{}", no_fragment, reference),
+            Some(_) => {
+                let dir = match path.rfind('/') {
+                    Some(i) => &path[..=i],
+                    None if self.authority.is_some() => "/",
+                    None => "",
+                };
+
+                format!(
+                    "{}{}",
+                    base,
+                    remove_dot_segments(&(dir.to_string() + &reference))
+                )
+            }
+        };
+
+        target.parse()
+    }
+
+    //Returns index in `inner`, at which the part following the authority starts.
+    fn authority_end(&self) -> usize {
+        match &self.authority {
+            Some(authority) => self.scheme.end + 3 + authority.inner.len(),
+            None => self.scheme.end + 1,
+        }
+    }
 }
 
 impl fmt::Display for Uri {
@@ -441,6 +501,64 @@ fn remove_spaces(text: &mut String) {
     text.retain(|c| !c.is_whitespace());
 }
 
+//Checks if `reference` starts with a scheme, i.e. it's not relative.
+fn has_scheme(reference: &str) -> bool {
+    match reference.find(':') {
+        Some(i) => {
+            let scheme = &reference[..i];
+
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        None => false,
+    }
+}
+
+//Removes `.` and `..` segments from the path of `reference`, leaving its query
+//and fragment untouched.
+fn remove_dot_segments(reference: &str) -> String {
+    let end = reference.find(&['?', '#'][..]).unwrap_or(reference.len());
+    let (path, rest) = reference.split_at(end);
+    let absolute = path.starts_with('/');
+
+    let mut segments = path.split('/').peekable();
+    let mut output = Vec::new();
+
+    if absolute {
+        segments.next();
+    }
+
+    while let Some(segment) = segments.next() {
+        let last = segments.peek().is_none();
+
+        match segment {
+            "." => {}
+            ".." => {
+                output.pop();
+            }
+            s => {
+                output.push(s);
+                continue;
+            }
+        }
+
+        if last {
+            output.push("");
+        }
+    }
+
+    let mut result = if absolute {
+        "/".to_string()
+    } else {
+        String::new()
+    };
+    result.push_str(&output.join("/"));
+    result.push_str(rest);
+    result
+}
+
 //Splits `s` by `separator`. If `separator` is found inside `s`, it will return two `Some` values
 //consisting `RangeC` of each `&str`. If `separator` is at the end of `s` or it's not found,
 //it will return tuple consisting `Some` with `RangeC` of entire `s` inside and None.
@@ -608,6 +726,11 @@ mod tests {
         assert_eq!(uris[2].corr_port(), HTTPS_PORT);
         assert_eq!(uris[3].corr_port(), HTTP_PORT);
         assert_eq!(uris[4].corr_port(), HTTPS_PORT);
+
+        let uri: Uri = "wss://example.com/chat".parse().unwrap();
+        assert_eq!(uri.corr_port(), HTTPS_PORT);
+        let uri: Uri = "ws://example.com/chat".parse().unwrap();
+        assert_eq!(uri.corr_port(), HTTP_PORT);
     }
 
     #[test]
@@ -669,6 +792,47 @@ mod tests {
         assert_eq!(uris[4].resource(), "/");
     }
 
+    #[test]
+    fn uri_join() {
+        let base: Uri = "http://a/b/c/d;p?q".parse().unwrap();
+        let cases = [
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("g/../h?x/../y", "http://a/b/c/h?x/../y"),
+            ("https://foo.com:12/bar", "https://foo.com:12/bar"),
+        ];
+
+        for (reference, expected) in cases.iter() {
+            assert_eq!(base.join(reference).unwrap().to_string(), *expected);
+        }
+
+        let base: Uri = "https://foo.com".parse().unwrap();
+        assert_eq!(base.join("bar").unwrap().to_string(), "https://foo.com/bar");
+        assert_eq!(base.join("?q").unwrap().to_string(), "https://foo.com?q");
+
+        let base: Uri = "https://foo.com/".parse().unwrap();
+        assert_eq!(base.join("bar").unwrap().to_string(), "https://foo.com/bar");
+        assert_eq!(
+            base.join("/bar").unwrap().to_string(),
+            "https://foo.com/bar"
+        );
+        assert_eq!(base.join("?q").unwrap().to_string(), "https://foo.com/?q");
+    }
+
     #[test]
     fn uri_display() {
         let uris: Vec<_> = TEST_URIS
@@ -0,0 +1,1142 @@
+//! WebSocket client, as described in RFC 6455
+use std::prelude::v1::*;
+use self::deflate::Deflate;
+use crate::{
+    error::{self, ParseErr},
+    request::{base64, Request, Upgraded},
+    response::Response,
+    uri::Uri,
+};
+use ring::{
+    digest::{digest, SHA1_FOR_LEGACY_USE_ONLY},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::io::{self, ErrorKind, Read, Write};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+const MAX_CONTROL_SIZE: usize = 125;
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+const RSV1: u8 = 0x40;
+
+///Message sent or received over a `WebSocket`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Message {
+    ///UTF-8 text
+    Text(String),
+    ///Binary data
+    Binary(Vec<u8>),
+    ///Ping with application data, up to 125 bytes. Pings received from the server
+    ///are answered automatically.
+    Ping(Vec<u8>),
+    ///Pong with application data, up to 125 bytes
+    Pong(Vec<u8>),
+    ///Request to close the connection, with an optional status code and reason
+    Close(Option<CloseFrame>),
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Message {
+        Message::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Message {
+        Message::Text(text.to_string())
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Message {
+        Message::Binary(data)
+    }
+}
+
+///Status code and reason of a `Close` message
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl CloseFrame {
+    ///Creates a new `CloseFrame` with the given status code and reason.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::websocket::CloseFrame;
+    ///
+    ///let frame = CloseFrame::new(1000, "done");
+    ///assert_eq!(frame.code, 1000);
+    ///```
+    pub fn new<T: ToString + ?Sized>(code: u16, reason: &T) -> CloseFrame {
+        CloseFrame {
+            code,
+            reason: reason.to_string(),
+        }
+    }
+
+    //Parses payload of a close frame. Fails with a status code for closing the connection.
+    fn parse(payload: &[u8]) -> Result<Option<CloseFrame>, u16> {
+        match payload {
+            [] => Ok(None),
+            [_] => Err(1002),
+            [a, b, reason @ ..] => {
+                let code = u16::from_be_bytes([*a, *b]);
+
+                if !matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999) {
+                    return Err(1002);
+                }
+
+                match String::from_utf8(reason.to_vec()) {
+                    Ok(reason) => Ok(Some(CloseFrame { code, reason })),
+                    Err(_) => Err(1007),
+                }
+            }
+        }
+    }
+}
+
+///WebSocket connection, opened with `connect` or `WebSocket::handshake`
+///
+///Messages are sent with `send` and received with `read`. Pings received from the server
+///are answered, and its close requests are confirmed, as part of `read`.
+///
+///With the `permessage-deflate` feature, compression of messages is offered
+///in the opening handshake and used if the server accepts it.
+pub struct WebSocket {
+    stream: Upgraded,
+    deflate: Option<Deflate>,
+    max_message_size: usize,
+    partial: Option<Partial>,
+    closing: bool,
+    closed: bool,
+}
+
+impl WebSocket {
+    ///Performs the opening handshake of a WebSocket connection with `request`,
+    ///which may use `ws`, `wss`, `http` or `https` uri, and returns
+    ///the connection along with the response of the server.
+    ///
+    ///Settings of `request`, e.g. headers, timeouts, proxy or TLS configuration, apply to
+    ///the handshake and the connection. A proxy is asked to open a tunnel with `CONNECT`,
+    ///also for `ws` uris. Subprotocols can be requested with
+    ///the `Sec-WebSocket-Protocol` header and the selected one read from the response.
+    ///
+    ///Fails with `ConnectionRefused` if the server doesn't switch protocols
+    ///and with `HeadersErr` if its response doesn't complete the handshake.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::{request::Request, uri::Uri, websocket::{Message, WebSocket}};
+    ///
+    ///let uri: Uri = "ws://localhost:8080/chat".parse().unwrap();
+    ///let mut request = Request::new(&uri);
+    ///request.header("Sec-WebSocket-Protocol", "chat");
+    ///
+    ///if let Ok((mut ws, _response)) = WebSocket::handshake(&request) {
+    ///    ws.send(Message::from("hello")).unwrap();
+    ///}
+    ///```
+    pub fn handshake(request: &Request) -> Result<(WebSocket, Response), error::Error> {
+        let mut key = [0; 16];
+        random_bytes(&mut key)?;
+        let key = base64(&key);
+
+        let mut request = request.clone();
+        request
+            .header("Upgrade", "websocket")
+            .header("Sec-WebSocket-Key", &key)
+            .header("Sec-WebSocket-Version", "13");
+
+        if let Some(offer) = deflate::OFFER {
+            request.header("Sec-WebSocket-Extensions", offer);
+        }
+
+        let mut body = Vec::new();
+        let (res, stream) = request.upgrade(&mut body)?;
+        let stream = match stream {
+            Some(stream) => stream,
+            None => {
+                return Err(io::Error::new(
+                    ErrorKind::ConnectionRefused,
+                    format!("server refused WebSocket handshake: {}", res.status_code()),
+                )
+                .into())
+            }
+        };
+
+        let headers = res.headers();
+        let upgrade = headers.get("Upgrade").map(String::as_str);
+        let connection = headers.get("Connection").map_or("", String::as_str);
+
+        if !matches!(upgrade, Some(protocol) if protocol.eq_ignore_ascii_case("websocket"))
+            || !connection
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+            || headers.get("Sec-WebSocket-Accept") != Some(&accept_key(&key))
+        {
+            return Err(ParseErr::HeadersErr.into());
+        }
+
+        let deflate = match headers.get("Sec-WebSocket-Extensions") {
+            Some(extensions) => match Deflate::accept(extensions) {
+                Some(deflate) => Some(deflate),
+                None => return Err(ParseErr::HeadersErr.into()),
+            },
+            None => None,
+        };
+
+        let ws = WebSocket {
+            stream,
+            deflate,
+            max_message_size: MAX_MESSAGE_SIZE,
+            partial: None,
+            closing: false,
+            closed: false,
+        };
+
+        Ok((ws, res))
+    }
+
+    ///Sets the maximum size of received messages in bytes, after decompression.
+    ///Larger messages close the connection with status 1009. Default is 64 MiB.
+    pub fn max_message_size(&mut self, size: usize) -> &mut Self {
+        self.max_message_size = size;
+        self
+    }
+
+    ///Checks if messages are compressed with `permessage-deflate`.
+    pub fn is_compressed(&self) -> bool {
+        self.deflate.is_some()
+    }
+
+    ///Returns a reference to the underlying connection.
+    pub fn get_ref(&self) -> &Upgraded {
+        &self.stream
+    }
+
+    ///Returns a mutable reference to the underlying connection. Reading from
+    ///or writing to it directly may corrupt the WebSocket stream.
+    pub fn get_mut(&mut self) -> &mut Upgraded {
+        &mut self.stream
+    }
+
+    ///Returns the underlying connection.
+    pub fn into_inner(self) -> Upgraded {
+        self.stream
+    }
+
+    ///Sends `message` in a single frame.
+    ///
+    ///Fails with `InvalidInput` if a control message carries more than 125 bytes
+    ///and with `NotConnected` once a `Close` message has been sent.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::websocket::{self, Message};
+    ///
+    ///if let Ok((mut ws, _)) = websocket::connect("ws://localhost:8080/") {
+    ///    ws.send(Message::Text("hello".to_string())).unwrap();
+    ///    ws.send(Message::Binary(vec![1, 2, 3])).unwrap();
+    ///}
+    ///```
+    pub fn send(&mut self, message: Message) -> io::Result<()> {
+        match message {
+            Message::Text(text) => self.send_data(TEXT, text.into_bytes(), usize::MAX),
+            Message::Binary(data) => self.send_data(BINARY, data, usize::MAX),
+            Message::Ping(data) => self.send_control(PING, data),
+            Message::Pong(data) => self.send_control(PONG, data),
+            Message::Close(frame) => {
+                let payload = frame.map_or_else(Vec::new, |frame| {
+                    let mut payload = frame.code.to_be_bytes().to_vec();
+                    payload.extend_from_slice(frame.reason.as_bytes());
+                    payload
+                });
+
+                self.send_control(CLOSE, payload)?;
+                self.closing = true;
+                Ok(())
+            }
+        }
+    }
+
+    ///Sends a `Text` or `Binary` message split into frames carrying
+    ///at most `fragment_size` bytes of (compressed) payload each.
+    ///
+    ///Fails with `InvalidInput` for control messages, which can't be fragmented.
+    pub fn send_fragmented(&mut self, message: Message, fragment_size: usize) -> io::Result<()> {
+        match message {
+            Message::Text(text) => self.send_data(TEXT, text.into_bytes(), fragment_size),
+            Message::Binary(data) => self.send_data(BINARY, data, fragment_size),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "control messages can't be fragmented",
+            )),
+        }
+    }
+
+    ///Reads the next message from the server, reassembling fragmented ones.
+    ///
+    ///Received `Ping` is answered with `Pong` before it's returned. Received `Close`
+    ///is confirmed, unless the client started closing, and ends the connection:
+    ///further reads fail with `NotConnected`.
+    ///
+    ///Violations of the protocol close the connection and fail with `InvalidData`.
+    pub fn read(&mut self) -> io::Result<Message> {
+        if self.closed {
+            return Err(not_connected());
+        }
+
+        loop {
+            let received = self.partial.as_ref().map_or(0, |p| p.data.len());
+            let frame = match Frame::read(&mut self.stream, self.max_message_size - received) {
+                Ok(frame) => frame,
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    return Err(self.fail(1009, "message too large"))
+                }
+                Err(e) => return Err(e),
+            };
+
+            let compressible = self.deflate.is_some() && matches!(frame.opcode, TEXT | BINARY);
+
+            if frame.mask.is_some() {
+                return Err(self.fail(1002, "frame from server is masked"));
+            }
+
+            if frame.rsv & !RSV1 != 0 || (frame.rsv & RSV1 != 0 && !compressible) {
+                return Err(self.fail(1002, "unexpected reserved bits"));
+            }
+
+            if frame.opcode >= CLOSE && (!frame.fin || frame.payload.len() > MAX_CONTROL_SIZE) {
+                return Err(self.fail(1002, "invalid control frame"));
+            }
+
+            match frame.opcode {
+                PING => {
+                    if !self.closing {
+                        self.send_frame(true, 0, PONG, frame.payload.clone())?;
+                    }
+
+                    return Ok(Message::Ping(frame.payload));
+                }
+                PONG => return Ok(Message::Pong(frame.payload)),
+                CLOSE => return self.on_close(&frame.payload),
+                CONTINUATION => match self.partial.as_mut() {
+                    Some(partial) => partial.data.extend_from_slice(&frame.payload),
+                    None => return Err(self.fail(1002, "unexpected continuation frame")),
+                },
+                TEXT | BINARY if self.partial.is_none() => {
+                    self.partial = Some(Partial {
+                        opcode: frame.opcode,
+                        compressed: frame.rsv & RSV1 != 0,
+                        data: frame.payload,
+                    });
+                }
+                TEXT | BINARY => return Err(self.fail(1002, "expected continuation frame")),
+                _ => return Err(self.fail(1002, "unknown opcode")),
+            }
+
+            if frame.fin {
+                if let Some(partial) = self.partial.take() {
+                    return self.message(partial);
+                }
+            }
+        }
+    }
+
+    ///Closes the connection: sends `Close` with an optional status code and reason,
+    ///unless it's already been sent, and reads messages until the server confirms it.
+    ///
+    ///# Examples
+    ///```
+    ///use http_req::websocket::{self, CloseFrame};
+    ///
+    ///if let Ok((mut ws, _)) = websocket::connect("ws://localhost:8080/") {
+    ///    ws.close(Some(CloseFrame::new(1000, "bye"))).unwrap();
+    ///}
+    ///```
+    pub fn close(&mut self, frame: Option<CloseFrame>) -> io::Result<()> {
+        if !self.closing {
+            self.send(Message::Close(frame))?;
+        }
+
+        while !self.closed {
+            self.read()?;
+        }
+
+        Ok(())
+    }
+
+    //Sends a data message, optionally compressed, in frames of up to `fragment_size` bytes.
+    fn send_data(&mut self, opcode: u8, data: Vec<u8>, fragment_size: usize) -> io::Result<()> {
+        let (rsv, data) = match self.deflate.as_mut() {
+            Some(deflate) => (RSV1, deflate.compress(&data)?),
+            None => (0, data),
+        };
+
+        if data.len() <= fragment_size {
+            return self.send_frame(true, rsv, opcode, data);
+        }
+
+        let mut fragments = data.chunks(fragment_size.max(1)).peekable();
+        let (mut rsv, mut opcode) = (rsv, opcode);
+
+        while let Some(fragment) = fragments.next() {
+            let fin = fragments.peek().is_none();
+            self.send_frame(fin, rsv, opcode, fragment.to_vec())?;
+
+            rsv = 0;
+            opcode = CONTINUATION;
+        }
+
+        Ok(())
+    }
+
+    //Sends a control frame, checking size of its payload.
+    fn send_control(&mut self, opcode: u8, payload: Vec<u8>) -> io::Result<()> {
+        if payload.len() > MAX_CONTROL_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "control message is longer than 125 bytes",
+            ));
+        }
+
+        self.send_frame(true, 0, opcode, payload)
+    }
+
+    //Masks and sends a single frame, unless the connection is closing.
+    fn send_frame(&mut self, fin: bool, rsv: u8, opcode: u8, payload: Vec<u8>) -> io::Result<()> {
+        if self.closing {
+            return Err(not_connected());
+        }
+
+        let mut mask = [0; 4];
+        random_bytes(&mut mask)?;
+
+        let frame = Frame {
+            fin,
+            rsv,
+            opcode,
+            mask: Some(mask),
+            payload,
+        };
+
+        frame.write(&mut self.stream)
+    }
+
+    //Handles close frame received from the server.
+    fn on_close(&mut self, payload: &[u8]) -> io::Result<Message> {
+        let frame = match CloseFrame::parse(payload) {
+            Ok(frame) => frame,
+            Err(code) => return Err(self.fail(code, "invalid close frame")),
+        };
+
+        if !self.closing {
+            let reply = frame
+                .as_ref()
+                .map_or_else(Vec::new, |frame| frame.code.to_be_bytes().to_vec());
+
+            self.send_frame(true, 0, CLOSE, reply)?;
+            self.closing = true;
+        }
+
+        self.closed = true;
+        Ok(Message::Close(frame))
+    }
+
+    //Turns a complete data message into `Message`, decompressing it if needed.
+    fn message(&mut self, partial: Partial) -> io::Result<Message> {
+        let data = match self.deflate.as_mut() {
+            Some(deflate) if partial.compressed => {
+                match deflate.decompress(&partial.data, self.max_message_size) {
+                    Ok(data) => data,
+                    Err(code) => return Err(self.fail(code, "invalid compressed message")),
+                }
+            }
+            _ => partial.data,
+        };
+
+        match partial.opcode {
+            TEXT => match String::from_utf8(data) {
+                Ok(text) => Ok(Message::Text(text)),
+                Err(_) => Err(self.fail(1007, "text message is not valid UTF-8")),
+            },
+            _ => Ok(Message::Binary(data)),
+        }
+    }
+
+    //Closes the connection with status `code` after a protocol violation.
+    fn fail(&mut self, code: u16, msg: &str) -> io::Error {
+        if !self.closing {
+            self.send_frame(true, 0, CLOSE, code.to_be_bytes().to_vec())
+                .ok();
+            self.closing = true;
+        }
+
+        self.closed = true;
+        io::Error::new(ErrorKind::InvalidData, msg)
+    }
+}
+
+///Opens a WebSocket connection to `uri`, which uses `ws` or `wss` scheme,
+///with default settings. Returns the connection and the response of the server.
+///
+///# Examples
+///```
+///use http_req::websocket::{self, Message};
+///
+///if let Ok((mut ws, _response)) = websocket::connect("ws://localhost:8080/echo") {
+///    ws.send(Message::from("hello")).unwrap();
+///    let reply = ws.read().unwrap();
+///}
+///```
+pub fn connect<T: AsRef<str>>(uri: T) -> Result<(WebSocket, Response), error::Error> {
+    let uri = uri.as_ref().parse::<Uri>()?;
+    WebSocket::handshake(&Request::new(&uri))
+}
+
+//Data message received so far
+struct Partial {
+    opcode: u8,
+    compressed: bool,
+    data: Vec<u8>,
+}
+
+//Single WebSocket frame
+#[derive(Debug, PartialEq, Clone)]
+struct Frame {
+    fin: bool,
+    rsv: u8,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    //Reads a frame, unmasking its payload. Fails with `InvalidData`
+    //if the payload is longer than `max_len`.
+    fn read<R: Read>(reader: &mut R, max_len: usize) -> io::Result<Frame> {
+        let mut head = [0; 2];
+        reader.read_exact(&mut head)?;
+
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                reader.read_exact(&mut len)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0; 8];
+                reader.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+
+        if len > max_len as u64 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "frame too large"));
+        }
+
+        let mask = if head[1] & 0x80 != 0 {
+            let mut mask = [0; 4];
+            reader.read_exact(&mut mask)?;
+            Some(mask)
+        } else {
+            None
+        };
+
+        let mut payload = vec![0; len as usize];
+        reader.read_exact(&mut payload)?;
+
+        if let Some(mask) = mask {
+            apply_mask(&mut payload, mask);
+        }
+
+        Ok(Frame {
+            fin: head[0] & 0x80 != 0,
+            rsv: head[0] & 0x70,
+            opcode: head[0] & 0x0F,
+            mask,
+            payload,
+        })
+    }
+
+    //Writes the frame, masking its payload if `mask` is set.
+    fn write<W: Write>(mut self, writer: &mut W) -> io::Result<()> {
+        let len = self.payload.len();
+        let mut buf = Vec::with_capacity(len + 14);
+        let mask_bit = if self.mask.is_some() { 0x80 } else { 0 };
+
+        let fin = if self.fin { 0x80 } else { 0 };
+
+        buf.push(fin | self.rsv | self.opcode);
+
+        if len < 126 {
+            buf.push(mask_bit | len as u8);
+        } else if len <= usize::from(u16::MAX) {
+            buf.push(mask_bit | 126);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            buf.push(mask_bit | 127);
+            buf.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+
+        if let Some(mask) = self.mask {
+            buf.extend_from_slice(&mask);
+            apply_mask(&mut self.payload, mask);
+        }
+
+        buf.extend_from_slice(&self.payload);
+        writer.write_all(&buf)?;
+        writer.flush()
+    }
+}
+
+//Masks or unmasks `data` in place.
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+fn not_connected() -> io::Error {
+    io::Error::new(ErrorKind::NotConnected, "WebSocket connection is closed")
+}
+
+//Fills `buf` with random bytes from the system's cryptographically secure generator,
+//as required for keys and masks by RFC 6455, 5.3.
+fn random_bytes(buf: &mut [u8]) -> io::Result<()> {
+    SystemRandom::new().fill(buf).map_err(|_| {
+        io::Error::new(
+            ErrorKind::Unsupported,
+            "secure random number generator is unavailable",
+        )
+    })
+}
+
+//Computes value of `Sec-WebSocket-Accept` expected for `key`.
+fn accept_key(key: &str) -> String {
+    base64(
+        digest(
+            &SHA1_FOR_LEGACY_USE_ONLY,
+            format!("{}{}", key, GUID).as_bytes(),
+        )
+        .as_ref(),
+    )
+}
+
+//Compression of messages with the `permessage-deflate` extension (RFC 7692)
+#[cfg(feature = "permessage-deflate")]
+mod deflate {
+    use miniz_oxide::{
+        deflate::{core::CompressorOxide, stream::deflate},
+        inflate::stream::{inflate, InflateState},
+        DataFormat, MZError, MZFlush, MZStatus,
+    };
+    use std::io::{self, ErrorKind};
+    use std::prelude::v1::*;
+
+    const TAIL: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+    const BUF_SIZE: usize = 8 * 1024;
+    const LEVEL: u8 = 6;
+
+    //Every message sent by the client is compressed separately, so it doesn't
+    //keep the compression context.
+    pub const OFFER: Option<&str> = Some("permessage-deflate; client_no_context_takeover");
+
+    pub struct Deflate {
+        inflater: Box<InflateState>,
+    }
+
+    impl Deflate {
+        //Accepts `extensions` selected by the server, if they're a valid response
+        //to `OFFER`. Decompression context is kept, so the server may use it or not.
+        pub fn accept(extensions: &str) -> Option<Deflate> {
+            let mut params = extensions.split(';').map(str::trim);
+
+            if params.next()? != "permessage-deflate" {
+                return None;
+            }
+
+            for param in params {
+                let name = param.split('=').next().unwrap_or("").trim();
+
+                if !matches!(
+                    name,
+                    "server_no_context_takeover"
+                        | "client_no_context_takeover"
+                        | "server_max_window_bits"
+                ) {
+                    return None;
+                }
+            }
+
+            Some(Deflate {
+                inflater: InflateState::new_boxed(DataFormat::Raw),
+            })
+        }
+
+        pub fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+            let mut compressor = CompressorOxide::default();
+            compressor.set_format_and_level(DataFormat::Raw, LEVEL);
+
+            let mut input = data;
+            let mut output = Vec::with_capacity(data.len() / 2 + TAIL.len());
+            let mut buf = vec![0; BUF_SIZE];
+
+            loop {
+                let res = deflate(&mut compressor, input, &mut buf, MZFlush::Sync);
+                input = &input[res.bytes_consumed..];
+                output.extend_from_slice(&buf[..res.bytes_written]);
+
+                match res.status {
+                    Ok(_) if input.is_empty() && res.bytes_written < buf.len() => break,
+                    Ok(_) => {}
+                    Err(MZError::Buf) if input.is_empty() => break,
+                    Err(_) => {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidInput,
+                            "cannot compress message",
+                        ))
+                    }
+                }
+            }
+
+            if output.ends_with(&TAIL) {
+                output.truncate(output.len() - TAIL.len());
+            }
+
+            Ok(output)
+        }
+
+        //Decompresses a message. Fails with a status code for closing the connection
+        //if it's invalid or larger than `max_size`.
+        pub fn decompress(&mut self, data: &[u8], max_size: usize) -> Result<Vec<u8>, u16> {
+            let data: Vec<u8> = data.iter().chain(TAIL.iter()).copied().collect();
+            let mut input = &data[..];
+            let mut output = Vec::new();
+            let mut buf = vec![0; BUF_SIZE];
+
+            loop {
+                let res = inflate(&mut self.inflater, input, &mut buf, MZFlush::None);
+                input = &input[res.bytes_consumed..];
+                output.extend_from_slice(&buf[..res.bytes_written]);
+
+                if output.len() > max_size {
+                    return Err(1009);
+                }
+
+                match res.status {
+                    Ok(MZStatus::StreamEnd) => {
+                        self.inflater.reset(DataFormat::Raw);
+                        break;
+                    }
+                    Ok(_) if input.is_empty() && res.bytes_written < buf.len() => break,
+                    Ok(_) => {}
+                    Err(MZError::Buf) if input.is_empty() => break,
+                    Err(_) => return Err(1007),
+                }
+            }
+
+            Ok(output)
+        }
+    }
+}
+
+//Stand-in used when the `permessage-deflate` feature is disabled: compression is never
+//offered, so it can't be accepted.
+#[cfg(not(feature = "permessage-deflate"))]
+mod deflate {
+    use std::io;
+    use std::prelude::v1::*;
+
+    pub const OFFER: Option<&str> = None;
+
+    pub enum Deflate {}
+
+    impl Deflate {
+        pub fn accept(_extensions: &str) -> Option<Deflate> {
+            None
+        }
+
+        pub fn compress(&mut self, _data: &[u8]) -> io::Result<Vec<u8>> {
+            match *self {}
+        }
+
+        pub fn decompress(&mut self, _data: &[u8], _max_size: usize) -> Result<Vec<u8>, u16> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{response::StatusCode, test_server};
+    use std::{net::TcpStream, thread::JoinHandle};
+
+    //Accepts a single WebSocket connection, completes the handshake with `extensions`
+    //and hands the stream over to `handler`. Returns uri of the server.
+    fn serve<F, T>(extensions: Option<&'static str>, handler: F) -> (String, JoinHandle<T>)
+    where
+        F: FnOnce(TcpStream) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (uri, handle) = test_server::serve_with("/chat", move |mut stream| {
+            let request = test_server::read_head(&mut stream).unwrap();
+            let key = request
+                .lines()
+                .find(|line| line.starts_with("Sec-WebSocket-Key: "))
+                .map(|line| &line[19..])
+                .unwrap();
+
+            let mut response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n",
+                accept_key(key)
+            );
+
+            if let Some(extensions) = extensions {
+                response += &format!("Sec-WebSocket-Extensions: {}\r\n", extensions);
+            }
+
+            response += "\r\n";
+            stream.write_all(response.as_bytes()).unwrap();
+
+            handler(stream)
+        });
+
+        (uri.to_string().replacen("http", "ws", 1), handle)
+    }
+
+    //Echoes frames received from the client, until it closes the connection.
+    //Returns the received frames.
+    fn echo(mut stream: TcpStream) -> Vec<Frame> {
+        let mut frames = Vec::new();
+
+        loop {
+            let frame = Frame::read(&mut stream, MAX_MESSAGE_SIZE).unwrap();
+            let mut reply = frame.clone();
+            reply.mask = None;
+
+            if reply.opcode == PING {
+                reply.opcode = PONG;
+            }
+
+            reply.write(&mut stream).unwrap();
+            frames.push(frame);
+
+            if frames.last().unwrap().opcode == CLOSE {
+                return frames;
+            }
+        }
+    }
+
+    fn frame(fin: bool, opcode: u8, payload: &[u8]) -> Frame {
+        Frame {
+            fin,
+            rsv: 0,
+            opcode,
+            mask: None,
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[test]
+    fn accept_key_rfc() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn frame_read_write() {
+        for len in [0, 125, 126, 65535, 65536].iter() {
+            let payload: Vec<u8> = (0..*len).map(|i| i as u8).collect();
+
+            for mask in [None, Some([1, 2, 3, 4])].iter() {
+                let frame = Frame {
+                    fin: true,
+                    rsv: RSV1,
+                    opcode: BINARY,
+                    mask: *mask,
+                    payload: payload.clone(),
+                };
+
+                let mut buf = Vec::new();
+                frame.clone().write(&mut buf).unwrap();
+
+                if mask.is_some() && *len > 0 {
+                    assert!(!buf.ends_with(&payload));
+                }
+
+                assert_eq!(Frame::read(&mut &buf[..], *len).unwrap(), frame);
+            }
+        }
+
+        let mut buf = Vec::new();
+        frame(true, TEXT, b"hello").write(&mut buf).unwrap();
+        assert_eq!(buf, b"\x81\x05hello");
+
+        let err = Frame::read(&mut &buf[..], 4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn close_frame_parse() {
+        assert_eq!(CloseFrame::parse(b""), Ok(None));
+        assert_eq!(
+            CloseFrame::parse(b"\x03\xe8bye"),
+            Ok(Some(CloseFrame::new(1000, "bye")))
+        );
+        assert_eq!(CloseFrame::parse(b"\x03"), Err(1002));
+        assert_eq!(CloseFrame::parse(b"\x03\xed"), Err(1002));
+        assert_eq!(CloseFrame::parse(b"\x03\xe8\xff"), Err(1007));
+    }
+
+    #[test]
+    fn websocket_echo() {
+        let (uri, handle) = serve(None, echo);
+        let (mut ws, res) = connect(&uri).unwrap();
+
+        assert_eq!(res.status_code(), StatusCode::new(101));
+        assert!(!ws.is_compressed());
+
+        ws.send(Message::from("hello")).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::from("hello"));
+
+        let data: Vec<u8> = (0..70_000).map(|i| i as u8).collect();
+        ws.send(Message::from(data.clone())).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::Binary(data));
+
+        ws.send_fragmented(Message::from("fragmented"), 4).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::from("fragmented"));
+
+        ws.send(Message::Ping(b"ping".to_vec())).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::Pong(b"ping".to_vec()));
+
+        ws.close(Some(CloseFrame::new(1000, "bye"))).unwrap();
+        assert_eq!(ws.read().unwrap_err().kind(), ErrorKind::NotConnected);
+        assert_eq!(
+            ws.send(Message::from("late")).unwrap_err().kind(),
+            ErrorKind::NotConnected
+        );
+
+        let frames = handle.join().unwrap();
+        let opcodes: Vec<(bool, u8)> = frames.iter().map(|f| (f.fin, f.opcode)).collect();
+
+        assert!(frames.iter().all(|f| f.mask.is_some()));
+        assert_eq!(
+            opcodes,
+            vec![
+                (true, TEXT),
+                (true, BINARY),
+                (false, TEXT),
+                (false, CONTINUATION),
+                (true, CONTINUATION),
+                (true, PING),
+                (true, CLOSE),
+            ]
+        );
+        assert_eq!(frames[6].payload, b"\x03\xe8bye");
+    }
+
+    #[test]
+    fn websocket_server_messages() {
+        let (uri, handle) = serve(None, |mut stream| {
+            frame(true, PING, b"1").write(&mut stream).unwrap();
+            frame(false, TEXT, b"hel").write(&mut stream).unwrap();
+            frame(true, PING, b"2").write(&mut stream).unwrap();
+            frame(true, CONTINUATION, b"lo").write(&mut stream).unwrap();
+            frame(true, CLOSE, b"\x03\xe9going away")
+                .write(&mut stream)
+                .unwrap();
+
+            let mut frames = Vec::new();
+            for _ in 0..3 {
+                frames.push(Frame::read(&mut stream, MAX_MESSAGE_SIZE).unwrap());
+            }
+            frames
+        });
+        let (mut ws, _) = connect(&uri).unwrap();
+
+        assert_eq!(ws.read().unwrap(), Message::Ping(b"1".to_vec()));
+        assert_eq!(ws.read().unwrap(), Message::Ping(b"2".to_vec()));
+        assert_eq!(ws.read().unwrap(), Message::from("hello"));
+        assert_eq!(
+            ws.read().unwrap(),
+            Message::Close(Some(CloseFrame::new(1001, "going away")))
+        );
+        assert_eq!(ws.read().unwrap_err().kind(), ErrorKind::NotConnected);
+
+        let frames = handle.join().unwrap();
+        assert_eq!(
+            (frames[0].opcode, &frames[0].payload[..]),
+            (PONG, &b"1"[..])
+        );
+        assert_eq!(
+            (frames[1].opcode, &frames[1].payload[..]),
+            (PONG, &b"2"[..])
+        );
+        assert_eq!(
+            (frames[2].opcode, &frames[2].payload[..]),
+            (CLOSE, &b"\x03\xe9"[..])
+        );
+    }
+
+    #[test]
+    fn websocket_protocol_error() {
+        const CASES: [(&[u8], u16); 5] = [
+            (b"\x81\x83\x01\x02\x03\x04abc", 1002),
+            (b"\x80\x01a", 1002),
+            (b"\xc1\x01a", 1002),
+            (b"\x81\x02\xff\xfe", 1007),
+            (b"\x09\x01a", 1002),
+        ];
+
+        for (data, code) in CASES.iter() {
+            let data = *data;
+            let (uri, handle) = serve(None, move |mut stream| {
+                stream.write_all(data).unwrap();
+                Frame::read(&mut stream, MAX_MESSAGE_SIZE).unwrap()
+            });
+            let (mut ws, _) = connect(&uri).unwrap();
+
+            assert_eq!(ws.read().unwrap_err().kind(), ErrorKind::InvalidData);
+            assert_eq!(ws.read().unwrap_err().kind(), ErrorKind::NotConnected);
+
+            let frame = handle.join().unwrap();
+            assert_eq!(frame.opcode, CLOSE);
+            assert_eq!(frame.payload, code.to_be_bytes());
+        }
+    }
+
+    #[test]
+    fn websocket_max_message_size() {
+        let (uri, handle) = serve(None, |mut stream| {
+            frame(false, BINARY, &[0; 6]).write(&mut stream).unwrap();
+            frame(true, CONTINUATION, &[0; 6])
+                .write(&mut stream)
+                .unwrap();
+            Frame::read(&mut stream, MAX_MESSAGE_SIZE).unwrap()
+        });
+        let (mut ws, _) = connect(&uri).unwrap();
+        ws.max_message_size(10);
+
+        assert_eq!(ws.read().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(handle.join().unwrap().payload, 1009u16.to_be_bytes());
+    }
+
+    #[test]
+    fn websocket_handshake_failed() {
+        let (uri, handle) = test_server::serve(
+            "/",
+            vec![
+                &b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"[..],
+                &b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                   Connection: Upgrade\r\nSec-WebSocket-Accept: invalid\r\n\r\n"[..],
+            ],
+        );
+        let uri = uri.to_string().replacen("http", "ws", 1);
+
+        match connect(&uri) {
+            Err(error::Error::IO(e)) => assert_eq!(e.kind(), ErrorKind::ConnectionRefused),
+            _ => panic!("handshake should be refused"),
+        }
+
+        match connect(&uri) {
+            Err(error::Error::Parse(e)) => assert_eq!(e, ParseErr::HeadersErr),
+            _ => panic!("handshake should fail"),
+        }
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn websocket_handshake_request() {
+        let (uri, handle) = serve(None, |_| ());
+        let uri: Uri = uri.parse().unwrap();
+        let mut request = Request::new(&uri);
+        request.header("Sec-WebSocket-Protocol", "chat");
+
+        let (_, res) = WebSocket::handshake(&request).unwrap();
+        assert_eq!(res.headers().get("Upgrade"), Some(&"websocket".to_string()));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn websocket_unknown_extension() {
+        let (uri, handle) = serve(Some("x-unknown"), |_| ());
+
+        match connect(&uri) {
+            Err(error::Error::Parse(e)) => assert_eq!(e, ParseErr::HeadersErr),
+            _ => panic!("extension should be rejected"),
+        }
+
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "permessage-deflate")]
+    #[test]
+    fn deflate_round_trip() {
+        let mut deflate = Deflate::accept("permessage-deflate").unwrap();
+        let data = b"hello hello hello hello hello".repeat(100);
+        let compressed = deflate.compress(&data).unwrap();
+
+        assert!(compressed.len() < data.len());
+        assert!(!compressed.ends_with(&[0x00, 0x00, 0xFF, 0xFF]));
+        assert_eq!(
+            deflate.decompress(&compressed, data.len()),
+            Ok(data.clone())
+        );
+        assert_eq!(deflate.decompress(&compressed, 100), Err(1009));
+
+        let mut deflate = Deflate::accept("permessage-deflate").unwrap();
+        assert_eq!(deflate.decompress(b"\xff\xff\xff", 100), Err(1007));
+
+        //message from RFC 7692, section 7.2.3.1
+        let mut deflate = Deflate::accept("permessage-deflate").unwrap();
+        assert_eq!(
+            deflate.decompress(b"\xf2\x48\xcd\xc9\xc9\x07\x00", 100),
+            Ok(b"Hello".to_vec())
+        );
+
+        assert!(Deflate::accept("permessage-deflate; server_no_context_takeover").is_some());
+        assert!(Deflate::accept("permessage-deflate; client_max_window_bits=10").is_none());
+        assert!(Deflate::accept("permessage-deflate, x-other").is_none());
+    }
+
+    #[cfg(feature = "permessage-deflate")]
+    #[test]
+    fn websocket_deflate_echo() {
+        let (uri, handle) = serve(Some("permessage-deflate; client_no_context_takeover"), echo);
+        let (mut ws, _) = connect(&uri).unwrap();
+        let text = "compressed ".repeat(1000);
+
+        assert!(ws.is_compressed());
+
+        ws.send(Message::from(text.as_str())).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::from(text.as_str()));
+
+        ws.send_fragmented(Message::from(text.as_str()), 8).unwrap();
+        assert_eq!(ws.read().unwrap(), Message::from(text.as_str()));
+
+        ws.close(None).unwrap();
+
+        let frames = handle.join().unwrap();
+        assert_eq!(frames[0].rsv, RSV1);
+        assert!(frames[0].payload.len() < text.len());
+        assert_eq!(frames[1].rsv, RSV1);
+        assert_eq!(frames[2].rsv, 0);
+    }
+}